@@ -1,23 +1,58 @@
 // Interaction with bluetooth
 
 use std::sync::Mutex;
-use arctic::{EventHandler, PmdRead, PolarSensor};
+use arctic::{EventHandler, HeartRate, PmdRead, PolarSensor};
+use serde::Serialize;
 use tokio::sync::watch;
 use std::{fs::{OpenOptions, File}, io::Write, io};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+// One line of output, e.g. {"received_ms":1656000000000,"heart_rate":{...}}
+#[derive(Serialize)]
+struct Record<'a> {
+    received_ms: u128,
+    #[serde(flatten)]
+    data: Data<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Data<'a> {
+    Measurement(&'a PmdRead),
+    HeartRate(&'a HeartRate),
+}
+
 struct Handler {
     rx: watch::Receiver<bool>,
     output: Mutex<File>,
 }
 
+impl Handler {
+    // Write `data` as a line of JSON
+    fn write(&self, data: Data<'_>) {
+        let record = Record {
+            received_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Error getting time")
+                .as_millis(),
+            data,
+        };
+        let mut output = self.output.lock().unwrap();
+        if serde_json::to_writer(&mut *output, &record).is_ok() {
+            let _ = output.write_all(b"\n");
+            let _ = output.flush();
+        }
+    }
+}
+
 #[arctic::async_trait]
 impl EventHandler for Handler {
+    async fn heart_rate_update(&self, _ctx: &PolarSensor, heartrate: HeartRate) {
+        self.write(Data::HeartRate(&heartrate));
+    }
+
     async fn measurement_update(&self, _ctx: &PolarSensor, data: PmdRead) {
-        let msg = format!("{:?}\n", data.data());
-        let mut output = self.output.lock().unwrap();
-        let _ = output.write_all(msg.as_bytes());
-        let _ = output.flush();
+        self.write(Data::Measurement(&data));
     }
 
     async fn should_continue(&self) -> bool {
@@ -35,7 +70,7 @@ impl Handler {
                 .as_secs()
                 .to_string(),
         );
-        output.push_str(".txt");
+        output.push_str(".ndjson");
 
         let open_attempt = OpenOptions::new().append(true).create(true).open(output.clone());
         let open_file = if open_attempt.is_err() {
@@ -64,6 +99,9 @@ pub async fn init(polar: &mut PolarSensor, rx: watch::Receiver<bool>) -> Result<
     if let Err(why) = polar.subscribe(arctic::NotifyStream::MeasurementData).await {
         eprintln!("Could not subscribe to measurment notifications: {:?}", why)
     }
+    if let Err(why) = polar.subscribe(arctic::NotifyStream::HeartRate).await {
+        eprintln!("Could not subscribe to heart rate notifications: {:?}", why)
+    }
 
     polar.event_handler(Handler::new(rx).await?);
 
@@ -0,0 +1,12 @@
+//! Runs every parser in `arctic` over arbitrary input, run with `cargo fuzz run parsers`
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = arctic::parse_pmd(data);
+    let _ = arctic::parse_heart_rate(data);
+    let _ = arctic::parse_spo2(data);
+    let _ = arctic::parse_control_response(data);
+    let _ = arctic::parse_stream_settings(data);
+});
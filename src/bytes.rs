@@ -0,0 +1,126 @@
+//! # Bytes
+//!
+//! Bytes contains helpers to read numbers out of GATT characteristics and PMD packets.
+//!
+//! Bluetooth sends multi-byte values least significant byte first, and Polar's PMD service
+//! follows the same order. Every PMD timestamp, sample and setting, heart rate RR interval and
+//! current time value is little endian, so parsing goes through these helpers rather than
+//! picking a byte order at each call site.
+//!
+//! Some standard GATT measurements (e.g. SpO2) use the 16 bit SFLOAT format, read with
+//! [`sfloat_le`].
+//!
+
+/// Read a little endian signed integer of 1 to 4 bytes, sign extending it to an [`i32`]
+///
+/// Panics if `data` is empty or longer than 4 bytes.
+pub(crate) fn i32_le(data: &[u8]) -> i32 {
+    assert!(
+        (1..=4).contains(&data.len()),
+        "Expected 1 to 4 bytes, got {}",
+        data.len()
+    );
+
+    let mut buf = [0u8; 4];
+    buf[..data.len()].copy_from_slice(data);
+
+    sign_extend(i32::from_le_bytes(buf), 8 * data.len() as u8)
+}
+
+/// Sign extend the low `bits` bits of `val` to an [`i32`]
+///
+/// Panics if `bits` is 0 or more than 32.
+pub(crate) fn sign_extend(val: i32, bits: u8) -> i32 {
+    assert!(
+        (1..=32).contains(&bits),
+        "Expected 1 to 32 bits, got {}",
+        bits
+    );
+
+    // Shift the value's sign bit up to bit 31 and back to extend it
+    let unused = 32 - u32::from(bits);
+    (val << unused) >> unused
+}
+
+/// Read a little endian [`u16`] from the first 2 bytes of `data`
+pub(crate) fn u16_le(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data[1]])
+}
+
+/// Read a little endian [`u64`] from the first 8 bytes of `data`
+pub(crate) fn u64_le(data: &[u8]) -> u64 {
+    u64::from_le_bytes(
+        data[..8]
+            .try_into()
+            .expect("Slice could not be converted to u64"),
+    )
+}
+
+/// Read a little endian SFLOAT from the first 2 bytes of `data`
+///
+/// An SFLOAT is a 12 bit signed mantissa times 10 to the power of a 4 bit signed exponent.
+/// Returns [`None`] for the special values (NaN, NRes, infinities and the reserved value).
+pub(crate) fn sfloat_le(data: &[u8]) -> Option<f32> {
+    let raw = u16_le(data);
+    let mantissa = i32::from(raw & 0x0fff);
+    if (0x07fe..=0x0802).contains(&mantissa) {
+        return None;
+    }
+
+    let mantissa = sign_extend(mantissa, 12);
+    let exponent = sign_extend(i32::from(raw >> 12), 4);
+    Some(mantissa as f32 * 10f32.powi(exponent))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_i24_to_i32() {
+        assert_eq!(-1, i32_le(&[0xff, 0xff, 0xff]));
+        assert_eq!(1_048_576, i32_le(&[0x00, 0x00, 0x10]));
+    }
+
+    #[test]
+    fn convert_i16_to_i32() {
+        assert_eq!(-1, i32_le(&[0xff, 0xff]));
+        assert_eq!(4096, i32_le(&[0x00, 0x10]));
+    }
+
+    #[test]
+    fn convert_i8_to_i32() {
+        assert_eq!(-1, i32_le(&[0xff]));
+        assert_eq!(16, i32_le(&[0x10]));
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(-1, sign_extend(0x3fff, 14));
+        assert_eq!(0x1fff, sign_extend(0x1fff, 14));
+        // Bits above `bits` are ignored
+        assert_eq!(-2, sign_extend(0x7ffe, 4));
+        assert_eq!(i32::MIN, sign_extend(i32::MIN, 32));
+    }
+
+    #[test]
+    fn convert_unsigned() {
+        assert_eq!(0x8001, u16_le(&[0x01, 0x80, 0xff]));
+        assert_eq!(
+            0x0852_458b_42a2_54ea,
+            u64_le(&[0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08])
+        );
+        assert_eq!(i32::MIN, i32_le(&[0x00, 0x00, 0x00, 0x80]));
+    }
+
+    #[test]
+    fn convert_sfloat() {
+        assert_eq!(sfloat_le(&[0x62, 0x00]), Some(98.0));
+        // 975 * 10^-1
+        assert_eq!(sfloat_le(&[0xcf, 0xf3]), Some(97.5));
+        // -1 * 10^2
+        assert_eq!(sfloat_le(&[0xff, 0x2f]), Some(-100.0));
+        assert_eq!(sfloat_le(&[0xff, 0x07]), None);
+        assert_eq!(sfloat_le(&[0x00, 0x08]), None);
+    }
+}
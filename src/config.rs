@@ -0,0 +1,53 @@
+//! # Config
+//!
+//! Config contains [`PolarConfig`], which holds everything needed to set up a [`crate::PolarSensor`] in one call.
+//!
+
+use crate::H10MeasurementType;
+
+use std::time::Duration;
+
+/// Settings used to create a fully set up [`crate::PolarSensor`] with [`crate::PolarSensor::with_config`]
+///
+/// With the `serde` feature enabled this can be deserialized, with any missing fields
+/// taking their default values.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PolarConfig {
+    /// The device id written on the device (e.g, "8C4CAD2D")
+    pub device_id: String,
+    /// How long to scan for the device when connecting
+    pub scan_duration: Duration,
+    /// Measurement types to start in the event loop
+    pub data_types: Vec<H10MeasurementType>,
+    /// Range of 2G, 4G or 8G (only for ACC)
+    pub range: u8,
+    /// Sample rate in hz (only for ACC)
+    pub sample_rate: u8,
+    /// Reconnect and resume the event loop if the connection drops
+    pub auto_reconnect: bool,
+}
+
+impl Default for PolarConfig {
+    fn default() -> Self {
+        PolarConfig {
+            device_id: String::new(),
+            scan_duration: Duration::from_secs(2),
+            data_types: vec![],
+            range: 8,
+            sample_rate: 200,
+            auto_reconnect: false,
+        }
+    }
+}
+
+impl PolarConfig {
+    /// Create a config for `device_id` with default settings
+    pub fn new(device_id: String) -> PolarConfig {
+        PolarConfig {
+            device_id,
+            ..Default::default()
+        }
+    }
+}
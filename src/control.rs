@@ -3,7 +3,8 @@
 //! Control contains structures related to sending and receiving messages over PMD control point.
 //!
 
-use crate::{find_characteristic, Error, H10MeasurementType, PolarResult};
+use crate::bytes::u16_le;
+use crate::{find_characteristic, Error, H10MeasurementType, ParseError, PolarResult};
 
 use btleplug::api::{Characteristic, Peripheral as _, WriteType};
 use btleplug::platform::Peripheral;
@@ -15,7 +16,8 @@ const PMD_CP_UUID: Uuid = Uuid::from_u128(0xfb005c81_02e7_f387_1cad_8acd2d8df0c8
 const PMD_DATA_UUID: Uuid = Uuid::from_u128(0xfb005c82_02e7_f387_1cad_8acd2d8df0c8);
 
 /// Command options to write to the control point
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ControlPointCommand {
     /// Do nothing
     Null = 0,
@@ -28,24 +30,36 @@ pub enum ControlPointCommand {
 }
 
 impl TryFrom<u8> for ControlPointCommand {
-    type Error = ();
+    type Error = Error;
 
-    fn try_from(val: u8) -> Result<ControlPointCommand, ()> {
+    /// Returns [`Error::UnknownOpCode`] holding `val` if it isn't a known op code
+    fn try_from(val: u8) -> PolarResult<ControlPointCommand> {
         match val {
             0 => Ok(ControlPointCommand::Null),
             1 => Ok(ControlPointCommand::GetMeasurementSettings),
             2 => Ok(ControlPointCommand::RequestMeasurementStart),
             3 => Ok(ControlPointCommand::StopMeasurement),
-            _ => {
-                println!("Invalid ControlPointCommand {}", val);
-                Err(())
-            }
+            _ => Err(Error::UnknownOpCode(val)),
         }
     }
 }
 
+impl ControlPointCommand {
+    /// Get the op code byte of this command
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl From<ControlPointCommand> for u8 {
+    fn from(item: ControlPointCommand) -> Self {
+        item.as_u8()
+    }
+}
+
 /// Response code returned after a write to PMD control point
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ControlPointResponseCode {
     /// Command was successful
     Success = 0,
@@ -96,33 +110,66 @@ impl TryFrom<u8> for ControlPointResponseCode {
             11 => Ok(ControlPointResponseCode::InvalidNumberOfChannels),
             12 => Ok(ControlPointResponseCode::InvalidState),
             13 => Ok(ControlPointResponseCode::DeviceInCharger),
-            _ => {
-                println!("Invalid ControlPointResponseCode {}", val);
-                Err(())
-            }
+            _ => Err(()),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum ResponseCode {
-    Success,
+impl ControlPointResponseCode {
+    /// Get the byte representing this response code
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl From<ControlPointResponseCode> for u8 {
+    fn from(item: ControlPointResponseCode) -> Self {
+        item.as_u8()
+    }
+}
+
+/// ATT error code a device answered a GATT request with
+///
+/// Returned in [`Error::AttError`] when the platform reports which ATT error caused a read or
+/// write to fail, which is usually a pairing or bonding problem.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResponseCode {
+    /// Request was successful
+    Success = 0,
+    /// The attribute handle is not valid on this device
     InvalidHandle,
+    /// The attribute can't be read
     ReadNotPermitted,
+    /// The attribute can't be written
     WriteNotPermitted,
+    /// The request was malformed
     InvalidPdu,
+    /// The device must be paired before the attribute can be used
     InsufficientAuthentication,
+    /// The device doesn't support the request
     RequestNotSupported,
+    /// The offset was past the end of the attribute
     InvalidOffset,
+    /// The host isn't authorized to use the attribute
     InsufficientAuthorization,
+    /// Too many prepared writes are queued
     PrepareQueueFull,
+    /// No attribute was found for the request
     AttributeNotFound,
+    /// The attribute can't be read or written with a blob request
     AttributeNotLong,
+    /// The encryption key used for the link is too short
     InsufficientEncryptionKeySize,
+    /// The value written has the wrong length for the attribute
     InsufficientAttributeValueLength,
+    /// The request failed for a reason with no other code
     UnlikelyError,
+    /// The link must be encrypted before the attribute can be used
     InsufficientEncryption,
+    /// The attribute type isn't a supported grouping attribute
     UnsupportedGroupType,
+    /// The device ran out of resources to handle the request
     InsufficientResources,
 }
 
@@ -149,14 +196,57 @@ impl TryFrom<u8> for ResponseCode {
             15 => Ok(ResponseCode::InsufficientEncryption),
             16 => Ok(ResponseCode::UnsupportedGroupType),
             17 => Ok(ResponseCode::InsufficientResources),
-            _ => {
-                println!("Invalid ResponseCode {}", val);
-                Err(())
-            }
+            _ => Err(()),
         }
     }
 }
 
+impl ResponseCode {
+    /// Get the byte representing this response code
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    // Find the ATT error behind a BLE error, if the platform reported one
+    //
+    // Only BlueZ passes the reason through, as D-Bus error messages. It names a few codes and
+    // gives the rest as "Operation failed with ATT error: 0x..".
+    fn from_ble_error(why: &btleplug::Error) -> Option<ResponseCode> {
+        let message = why.to_string();
+        if let Some((_, code)) = message.split_once("ATT error: 0x") {
+            let code = u8::from_str_radix(code.get(..2)?, 16).ok()?;
+            return ResponseCode::try_from(code).ok();
+        }
+
+        if message.contains("Read not permitted") {
+            Some(ResponseCode::ReadNotPermitted)
+        } else if message.contains("Write not permitted") {
+            Some(ResponseCode::WriteNotPermitted)
+        } else if message.contains("Not paired") {
+            Some(ResponseCode::InsufficientAuthentication)
+        } else if message.contains("org.bluez.Error.NotAuthorized") {
+            Some(ResponseCode::InsufficientAuthorization)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<ResponseCode> for u8 {
+    fn from(item: ResponseCode) -> Self {
+        item.as_u8()
+    }
+}
+
+/// Turn a BLE error into [`Error::AttError`] if it was caused by an ATT error, or
+/// [`Error::BleError`] otherwise
+pub(crate) fn ble_error(why: btleplug::Error) -> Error {
+    match ResponseCode::from_ble_error(&why) {
+        Some(code) => Error::AttError(code),
+        None => Error::BleError(why),
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 enum SettingType {
     SampleRate,
@@ -184,9 +274,10 @@ enum PmdByteType {
 #[derive(Debug, PartialEq, Eq)]
 pub struct StreamSettings {
     ty: H10MeasurementType,
-    resolution: u8,
+    resolutions: Vec<u8>,
     range: Option<Vec<u8>>,
     sample_rate: Vec<u8>,
+    sample_rates: Vec<u16>,
 }
 
 impl StreamSettings {
@@ -196,15 +287,20 @@ impl StreamSettings {
             return Err(Error::WrongResponse);
         }
 
-        let mut resolution: u8 = 0;
+        let mut resolutions: Vec<u8> = vec![];
         let mut ranges: Vec<u8> = vec![];
         let mut sample_rate: Vec<u8> = vec![];
+        let mut sample_rates: Vec<u16> = vec![];
 
-        let mut setting: SettingType = SettingType::from(resp.parameters[0]);
+        let (first, rest) = resp
+            .parameters()
+            .split_first()
+            .ok_or(Error::InvalidLength)?;
+        let mut setting: SettingType = SettingType::from(*first);
         let mut next_byte: PmdByteType = PmdByteType::ArrLen;
         let mut len_remaining = 0u8;
 
-        let mut data = resp.parameters()[1..].iter();
+        let mut data = rest.iter();
 
         while let Some(i) = data.next() {
             match next_byte {
@@ -214,21 +310,26 @@ impl StreamSettings {
                 }
                 PmdByteType::ArrLen => {
                     len_remaining = *i;
-                    next_byte = PmdByteType::Data;
+                    next_byte = if len_remaining == 0 {
+                        PmdByteType::Setting
+                    } else {
+                        PmdByteType::Data
+                    };
                 }
                 PmdByteType::Data => {
                     match setting {
                         SettingType::SampleRate => {
                             sample_rate.push(*i);
-                            let _ = data.next().unwrap();
+                            let high = *data.next().ok_or(Error::InvalidLength)?;
+                            sample_rates.push(u16_le(&[*i, high]));
                         }
                         SettingType::Resolution => {
-                            resolution = *i;
-                            let _ = data.next().unwrap();
+                            resolutions.push(*i);
+                            data.next().ok_or(Error::InvalidLength)?;
                         }
                         SettingType::Range => {
                             ranges.push(*i);
-                            let _ = data.next().unwrap();
+                            data.next().ok_or(Error::InvalidLength)?;
                         }
                     }
 
@@ -248,15 +349,24 @@ impl StreamSettings {
 
         Ok(StreamSettings {
             ty: *resp.data_type(),
-            resolution,
+            resolutions,
             range,
             sample_rate,
+            sample_rates,
         })
     }
 
     /// Getter for the resolution (in bits)
+    ///
+    /// If the device reports several resolutions this is the first one, see
+    /// [`StreamSettings::supported_resolutions`] for all of them. Returns 0 if none were reported.
     pub fn resolution(&self) -> u8 {
-        self.resolution
+        self.resolutions.first().copied().unwrap_or(0)
+    }
+
+    /// Get the supported resolutions (in bits)
+    pub fn supported_resolutions(&self) -> Vec<u8> {
+        self.resolutions.clone()
     }
 
     /// Getter for range (ACC only) (in G)
@@ -265,35 +375,161 @@ impl StreamSettings {
     }
 
     /// Getter for sample rates (in Hz)
+    ///
+    /// These are the raw low bytes the device sent, see [`StreamSettings::supported_sample_rates`]
+    /// for the full values.
     pub fn sample_rate(&self) -> &Vec<u8> {
         &self.sample_rate
     }
+
+    /// Get the supported sample rates (in Hz)
+    pub fn supported_sample_rates(&self) -> Vec<u16> {
+        self.sample_rates.clone()
+    }
+
+    /// Get the supported ranges (in G), empty if the measurement has no range setting
+    pub fn supported_ranges(&self) -> Vec<u8> {
+        self.range.clone().unwrap_or_default()
+    }
+
+    /// Build the control point command starting this measurement with the chosen settings
+    ///
+    /// `range` (in G) is needed when the measurement has a range setting and must be [`None`]
+    /// otherwise. The command holds the settings in the same order as [`crate::H10Profile`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidData`] if `sample_rate`, `range` or `resolution` isn't one
+    /// these settings support.
+    pub fn to_start_command(
+        &self,
+        sample_rate: u16,
+        range: Option<u8>,
+        resolution: u8,
+    ) -> PolarResult<Vec<u8>> {
+        let range_supported = match (&self.range, range) {
+            (Some(ranges), Some(range)) => ranges.contains(&range),
+            (None, None) => true,
+            _ => false,
+        };
+        if !range_supported
+            || !self.sample_rates.contains(&sample_rate)
+            || !self.resolutions.contains(&resolution)
+        {
+            return Err(Error::InvalidData);
+        }
+
+        let mut command = vec![
+            ControlPointCommand::RequestMeasurementStart.as_u8(),
+            self.ty.as_u8(),
+        ];
+
+        // Each setting is [setting type, count, u16 value]
+        if let Some(range) = range {
+            command.extend([0x02, 0x01, range, 0x00]);
+        }
+        command.extend([0x00, 0x01]);
+        command.extend(sample_rate.to_le_bytes());
+        command.extend([0x01, 0x01, resolution, 0x00]);
+
+        Ok(command)
+    }
 }
 
 /// Store data returned from the device after a write to the control point
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ControlResponse {
     opcode: ControlPointCommand,
     measurement_type: H10MeasurementType,
     status: ControlPointResponseCode,
+    more_frames: bool,
     parameters: Vec<u8>,
 }
 
 impl ControlResponse {
     /// Create new `ControlResponse`
     pub async fn new(data: Vec<u8>) -> PolarResult<ControlResponse> {
-        // We need at least 4 bytes for a complete packet
-        if data.len() < 4 {
-            return Err(Error::InvalidData);
+        ControlResponse::try_from(data.as_slice())
+    }
+
+    /// Return extra parameters of this response
+    pub fn parameters(&self) -> &Vec<u8> {
+        &self.parameters
+    }
+
+    /// Return opcode of this response
+    pub fn opcode(&self) -> &ControlPointCommand {
+        &self.opcode
+    }
+
+    /// Get measurement type
+    pub fn data_type(&self) -> &H10MeasurementType {
+        &self.measurement_type
+    }
+
+    /// Get response status
+    pub fn status(&self) -> &ControlPointResponseCode {
+        &self.status
+    }
+
+    /// Returns [`true`] if the device will send more frames to complete this response
+    ///
+    /// The parameters of each frame need to be joined to get the full response, which
+    /// [`crate::PolarSensor`] does before returning a response.
+    pub fn has_more_frames(&self) -> bool {
+        self.more_frames
+    }
+
+    // Add the parameters of the next frame of this response
+    pub(crate) fn append_frame(&mut self, frame: ControlResponse) {
+        self.parameters.extend(frame.parameters);
+        self.more_frames = frame.more_frames;
+    }
+
+    /// Get the sample rate (in Hz) reported in the parameters of this response, if any
+    ///
+    /// Start responses may echo the settings the device actually applied, which can
+    /// differ from what was requested.
+    pub fn sample_rate(&self) -> Option<u16> {
+        let params = &self.parameters;
+        let mut pos = 0;
+
+        // parameters are stored as [setting type, count, count * u16 values]
+        while pos + 1 < params.len() {
+            let setting = params[pos];
+            let count = params[pos + 1] as usize;
+            let values = pos + 2;
+
+            if let SettingType::SampleRate = SettingType::from(setting) {
+                if count > 0 && values + 2 <= params.len() {
+                    return Some(u16_le(&params[values..]));
+                }
+                return None;
+            }
+
+            pos = values + count * 2;
         }
+
+        None
+    }
+}
+
+impl TryFrom<&[u8]> for ControlResponse {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> PolarResult<ControlResponse> {
+        // We need at least 4 bytes for a complete packet
+        ParseError::check("control point response", data, 0, 4)?;
         // check that our response is a control point response
         if data[0] != 0xf0 {
             return Err(Error::InvalidData);
         }
-        let opcode = ControlPointCommand::try_from(data[1]).map_err(|_| Error::InvalidData)?;
+        let opcode = ControlPointCommand::try_from(data[1])?;
         let measurement_type =
             H10MeasurementType::try_from(data[2]).map_err(|_| Error::InvalidData)?;
         let status = ControlPointResponseCode::try_from(data[3]).map_err(|_| Error::InvalidData)?;
+        let more_frames = data.get(4).is_some_and(|more| *more != 0);
         let parameters = if data.len() > 5 {
             data[5..].to_vec()
         } else {
@@ -304,29 +540,22 @@ impl ControlResponse {
             opcode,
             measurement_type,
             status,
+            more_frames,
             parameters,
         })
     }
+}
 
-    /// Return extra parameters of this response
-    pub fn parameters(&self) -> &Vec<u8> {
-        &self.parameters
-    }
-
-    /// Return opcode of this response
-    pub fn opcode(&self) -> &ControlPointCommand {
-        &self.opcode
-    }
-
-    /// Get measurement type
-    pub fn data_type(&self) -> &H10MeasurementType {
-        &self.measurement_type
-    }
-
-    /// Get response status
-    pub fn status(&self) -> &ControlPointResponseCode {
-        &self.status
-    }
+/// How commands are written to the PMD control point
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ControlWriteType {
+    /// Write and wait for the device to acknowledge the write
+    #[default]
+    WithResponse,
+    /// Write without waiting for an acknowledgement
+    WithoutResponse,
+    /// Write with response, and retry without response if that write fails
+    Fallback,
 }
 
 /// Struct that has access to the PMD control point point and PMD data
@@ -334,6 +563,7 @@ impl ControlResponse {
 pub struct ControlPoint {
     control_point: Characteristic,
     measurement_data: Characteristic,
+    write_type: ControlWriteType,
 }
 
 impl ControlPoint {
@@ -345,29 +575,68 @@ impl ControlPoint {
         Ok(ControlPoint {
             control_point,
             measurement_data,
+            write_type: ControlWriteType::default(),
         })
     }
 
+    /// Get the write type used by [`ControlPoint::send_command`]
+    pub fn write_type(&self) -> ControlWriteType {
+        self.write_type
+    }
+
+    /// Set the write type used by [`ControlPoint::send_command`]
+    pub fn set_write_type(&mut self, write_type: ControlWriteType) {
+        self.write_type = write_type;
+    }
+
     /// Send command to Control Point
     pub async fn send_command(&self, device: &Peripheral, data: Vec<u8>) -> PolarResult<()> {
-        self.write(device, data).await?;
+        self.send_command_with(device, data, self.write_type).await
+    }
 
-        Ok(())
+    /// Send command to Control Point using a specific write type
+    pub async fn send_command_with(
+        &self,
+        device: &Peripheral,
+        data: Vec<u8>,
+        write_type: ControlWriteType,
+    ) -> PolarResult<()> {
+        match write_type {
+            ControlWriteType::WithResponse => {
+                self.write(device, &data, WriteType::WithResponse).await
+            }
+            ControlWriteType::WithoutResponse => {
+                self.write(device, &data, WriteType::WithoutResponse).await
+            }
+            ControlWriteType::Fallback => {
+                if self
+                    .write(device, &data, WriteType::WithResponse)
+                    .await
+                    .is_ok()
+                {
+                    return Ok(());
+                }
+
+                self.write(device, &data, WriteType::WithoutResponse).await
+            }
+        }
     }
 
-    async fn write(&self, device: &Peripheral, data: Vec<u8>) -> PolarResult<()> {
+    async fn write(
+        &self,
+        device: &Peripheral,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> PolarResult<()> {
         device
-            .write(&self.control_point, &data, WriteType::WithResponse)
+            .write(&self.control_point, data, write_type)
             .await
-            .map_err(Error::BleError)
+            .map_err(ble_error)
     }
 
     /// Read data from control point (for reading the features of a device)
     pub async fn read(&self, device: &Peripheral) -> PolarResult<Vec<u8>> {
-        device
-            .read(&self.control_point)
-            .await
-            .map_err(Error::BleError)
+        device.read(&self.control_point).await.map_err(ble_error)
     }
 }
 
@@ -386,9 +655,10 @@ mod test {
     fn settings_ecg() {
         let norm = StreamSettings {
             ty: H10MeasurementType::Ecg,
-            resolution: 14,
+            resolutions: vec![14],
             range: None,
             sample_rate: vec![130],
+            sample_rates: vec![130],
         };
 
         let data = aw!(ControlResponse::new(vec![
@@ -403,9 +673,10 @@ mod test {
     fn settings_acc() {
         let norm = StreamSettings {
             ty: H10MeasurementType::Acc,
-            resolution: 16,
+            resolutions: vec![16],
             range: Some(vec![2, 4, 8]),
             sample_rate: vec![25, 50, 100, 200],
+            sample_rates: vec![25, 50, 100, 200],
         };
 
         let data = aw!(ControlResponse::new(vec![
@@ -415,5 +686,192 @@ mod test {
         .unwrap();
 
         assert_eq!(norm, StreamSettings::new(&data).unwrap());
+        assert_eq!(norm.supported_sample_rates(), vec![25, 50, 100, 200]);
+        assert_eq!(norm.supported_ranges(), vec![2, 4, 8]);
+    }
+
+    #[test]
+    fn settings_resolutions() {
+        let data = aw!(ControlResponse::new(vec![
+            0xf0, 0x01, 0x02, 0x00, 0x00, 0x00, 0x01, 0x32, 0x00, 0x01, 0x02, 0x08, 0x00, 0x10,
+            0x00
+        ]))
+        .unwrap();
+        let settings = StreamSettings::new(&data).unwrap();
+
+        assert_eq!(settings.supported_resolutions(), vec![8, 16]);
+        assert_eq!(settings.resolution(), 8);
+        assert_eq!(
+            settings.to_start_command(50, None, 16).unwrap(),
+            vec![0x02, 0x02, 0x00, 0x01, 0x32, 0x00, 0x01, 0x01, 0x10, 0x00]
+        );
+        assert!(settings.to_start_command(50, None, 8).is_ok());
+        assert!(matches!(
+            settings.to_start_command(50, None, 12),
+            Err(Error::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn settings_start_command() {
+        use crate::{DeviceProfile, H10Profile};
+
+        let acc = aw!(ControlResponse::new(vec![
+            0xf0, 0x01, 0x02, 0x00, 0x00, 0x00, 0x04, 0x19, 0x00, 0x32, 0x00, 0x64, 0x00, 0xC8,
+            0x00, 0x01, 0x01, 0x10, 0x00, 0x02, 0x03, 0x02, 0x00, 0x04, 0x00, 0x08, 0x00
+        ]))
+        .unwrap();
+        let acc = StreamSettings::new(&acc).unwrap();
+        assert_eq!(
+            acc.to_start_command(100, Some(4), 16).unwrap(),
+            H10Profile.start_command(H10MeasurementType::Acc, 4, 100, 16)
+        );
+        assert!(matches!(
+            acc.to_start_command(120, Some(4), 16),
+            Err(Error::InvalidData)
+        ));
+        assert!(matches!(
+            acc.to_start_command(100, Some(16), 16),
+            Err(Error::InvalidData)
+        ));
+        assert!(matches!(
+            acc.to_start_command(100, None, 16),
+            Err(Error::InvalidData)
+        ));
+        assert!(matches!(
+            acc.to_start_command(100, Some(4), 8),
+            Err(Error::InvalidData)
+        ));
+
+        let ecg = aw!(ControlResponse::new(vec![
+            0xf0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x82, 0x00, 0x01, 0x01, 0x0e, 0x00
+        ]))
+        .unwrap();
+        let ecg = StreamSettings::new(&ecg).unwrap();
+        assert_eq!(
+            ecg.to_start_command(130, None, 14).unwrap(),
+            H10Profile.start_command(H10MeasurementType::Ecg, 8, 200, 16)
+        );
+        assert!(matches!(
+            ecg.to_start_command(130, Some(8), 14),
+            Err(Error::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn settings_wide_sample_rate() {
+        let data = aw!(ControlResponse::new(vec![
+            0xf0, 0x01, 0x02, 0x00, 0x00, 0x00, 0x02, 0x34, 0x00, 0xa0, 0x01, 0x01, 0x01, 0x10,
+            0x00
+        ]))
+        .unwrap();
+        let settings = StreamSettings::new(&data).unwrap();
+
+        assert_eq!(settings.supported_sample_rates(), vec![52, 416]);
+        assert_eq!(settings.sample_rate(), &vec![0x34, 0xa0]);
+        assert!(settings.supported_ranges().is_empty());
+    }
+
+    #[test]
+    fn command_round_trip() {
+        for cmd in [
+            ControlPointCommand::Null,
+            ControlPointCommand::GetMeasurementSettings,
+            ControlPointCommand::RequestMeasurementStart,
+            ControlPointCommand::StopMeasurement,
+        ] {
+            assert_eq!(ControlPointCommand::try_from(cmd.as_u8()).unwrap(), cmd);
+            assert_eq!(u8::from(cmd), cmd.as_u8());
+        }
+
+        assert!(matches!(
+            ControlPointCommand::try_from(0x80),
+            Err(Error::UnknownOpCode(0x80))
+        ));
+        assert!(matches!(
+            ControlResponse::try_from([0xf0, 0x07, 0x00, 0x00].as_slice()),
+            Err(Error::UnknownOpCode(0x07))
+        ));
+    }
+
+    #[test]
+    fn att_error_from_ble_error() {
+        let ble = |message: &str| btleplug::Error::Other(message.to_string().into());
+
+        assert!(matches!(
+            ble_error(ble("org.bluez.Error.NotPermitted: Write not permitted")),
+            Error::AttError(ResponseCode::WriteNotPermitted)
+        ));
+        assert!(matches!(
+            ble_error(ble("org.bluez.Error.NotPermitted: Not paired")),
+            Error::AttError(ResponseCode::InsufficientAuthentication)
+        ));
+        assert!(matches!(
+            ble_error(ble(
+                "org.bluez.Error.Failed: Operation failed with ATT error: 0x0f"
+            )),
+            Error::AttError(ResponseCode::InsufficientEncryption)
+        ));
+        assert!(matches!(
+            ble_error(ble("Operation failed with ATT error: 0x80")),
+            Error::BleError(_)
+        ));
+        assert!(matches!(
+            ble_error(btleplug::Error::NotConnected),
+            Error::BleError(_)
+        ));
+
+        for val in 0..=17u8 {
+            assert_eq!(ResponseCode::try_from(val).unwrap().as_u8(), val);
+        }
+        assert_eq!(ResponseCode::try_from(18), Err(()));
+    }
+
+    #[test]
+    fn response_code_round_trip() {
+        for val in 0..=13u8 {
+            let code = ControlPointResponseCode::try_from(val).unwrap();
+            assert_eq!(code.as_u8(), val);
+            assert_eq!(ControlPointResponseCode::try_from(code.as_u8()), Ok(code));
+        }
+        assert_eq!(ControlPointResponseCode::DeviceInCharger.as_u8(), 13);
+    }
+
+    #[test]
+    fn response_from_slice() {
+        let data: &[u8] = &[0xf0, 0x02, 0x02, 0x00, 0x00, 0x00, 0x01, 0x32, 0x00];
+        let response = ControlResponse::try_from(data).unwrap();
+
+        assert_eq!(
+            *response.opcode(),
+            ControlPointCommand::RequestMeasurementStart
+        );
+        assert_eq!(*response.status(), ControlPointResponseCode::Success);
+        assert_eq!(response.sample_rate(), Some(50));
+        assert!(!response.has_more_frames());
+        assert!(ControlResponse::try_from(&data[..3]).is_err());
+    }
+
+    #[test]
+    fn response_more_frames() {
+        let data: &[u8] = &[0xf0, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0x32, 0x00];
+        let response = ControlResponse::try_from(data).unwrap();
+        assert!(response.has_more_frames());
+        assert_eq!(*response.parameters(), vec![0x00, 0x01, 0x32, 0x00]);
+
+        let response = ControlResponse::try_from(&data[..4]).unwrap();
+        assert!(!response.has_more_frames());
+    }
+
+    #[test]
+    fn response_sample_rate() {
+        let data = aw!(ControlResponse::new(vec![
+            0xf0, 0x02, 0x02, 0x00, 0x00, 0x02, 0x01, 0x08, 0x00, 0x00, 0x01, 0x64, 0x00
+        ]))
+        .unwrap();
+        assert_eq!(data.sample_rate(), Some(100));
+
+        let data = aw!(ControlResponse::new(vec![0xf0, 0x02, 0x02, 0x00, 0x00])).unwrap();
+        assert_eq!(data.sample_rate(), None);
     }
 }
@@ -0,0 +1,141 @@
+//! # Device time
+//!
+//! Device time contains the conversion between [`SystemTime`] and the bluetooth
+//! Current Time characteristic, which is made up of a calendar date and time in the device's
+//! local time. The host's time zone is used to convert it.
+//!
+
+use crate::bytes::u16_le;
+use crate::{Error, ParseError, PolarResult};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Timelike, Utc};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Decode a Current Time characteristic value, as local time of the host
+pub(crate) fn decode(data: &[u8]) -> PolarResult<SystemTime> {
+    decode_in(data, &Local)
+}
+
+/// Encode a Current Time characteristic value in local time of the host
+pub(crate) fn encode(time: SystemTime) -> PolarResult<Vec<u8>> {
+    encode_in(time, &Local)
+}
+
+// Decode a Current Time characteristic value holding a date and time in `tz`
+fn decode_in<Tz: TimeZone>(data: &[u8], tz: &Tz) -> PolarResult<SystemTime> {
+    ParseError::check("current time", data, 0, 7)?;
+
+    // A year of 0 means the device doesn't know the date
+    let year = u16_le(data);
+    if year == 0 {
+        return Err(Error::InvalidData);
+    }
+
+    let time = NaiveDate::from_ymd_opt(i32::from(year), u32::from(data[2]), u32::from(data[3]))
+        .and_then(|date| {
+            date.and_hms_opt(u32::from(data[4]), u32::from(data[5]), u32::from(data[6]))
+        })
+        .ok_or(Error::InvalidData)?;
+    // Times skipped by a daylight saving change don't exist, repeated ones take the first
+    let time = tz
+        .from_local_datetime(&time)
+        .earliest()
+        .ok_or(Error::InvalidData)?;
+
+    // Fractions of a second are in 1/256ths
+    let fraction = data
+        .get(8)
+        .map_or(0, |f| u64::from(*f) * 1_000_000_000 / 256);
+
+    Ok(SystemTime::from(time) + Duration::from_nanos(fraction))
+}
+
+// Encode a Current Time characteristic value holding `time` in `tz`, with a manual time
+// update as the reason
+fn encode_in<Tz: TimeZone>(time: SystemTime, tz: &Tz) -> PolarResult<Vec<u8>> {
+    if time < UNIX_EPOCH {
+        return Err(Error::InvalidData);
+    }
+
+    let time = DateTime::<Utc>::from(time).with_timezone(tz);
+    let year = u16::try_from(time.year()).map_err(|_| Error::InvalidData)?;
+    let fraction = (u64::from(time.timestamp_subsec_nanos()) * 256 / 1_000_000_000) as u8;
+
+    let mut data = year.to_le_bytes().to_vec();
+    data.extend_from_slice(&[
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+        time.weekday().number_from_monday() as u8,
+        fraction,
+        0x01,
+    ]);
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn encode_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_615_734_566_500);
+
+        assert_eq!(
+            encode_in(time, &Utc).unwrap(),
+            vec![0xe5, 0x07, 3, 14, 15, 9, 26, 7, 128, 0x01]
+        );
+        // Two hours ahead of UTC
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(
+            encode_in(time, &tz).unwrap(),
+            vec![0xe5, 0x07, 3, 14, 17, 9, 26, 7, 128, 0x01]
+        );
+        assert!(matches!(
+            encode(UNIX_EPOCH - Duration::from_secs(1)),
+            Err(Error::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn decode_time() {
+        let data = [0xe5, 0x07, 3, 14, 15, 9, 26, 7, 128, 0x01];
+        assert_eq!(
+            decode_in(&data, &Utc).unwrap(),
+            UNIX_EPOCH + Duration::from_millis(1_615_734_566_500)
+        );
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(
+            decode_in(&data, &tz).unwrap(),
+            UNIX_EPOCH + Duration::from_millis(1_615_734_566_500 - 2 * 3_600_000)
+        );
+
+        assert_eq!(
+            decode_in(&[0xb2, 0x07, 1, 1, 0, 0, 0], &Utc).unwrap(),
+            UNIX_EPOCH
+        );
+        // Unknown year
+        assert!(matches!(
+            decode(&[0x00, 0x00, 3, 14, 15, 9, 26]),
+            Err(Error::InvalidData)
+        ));
+        assert!(matches!(
+            decode(&[0xe5, 0x07, 13, 14, 15, 9]),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            decode(&[0xe5, 0x07, 13, 14, 15, 9, 26]),
+            Err(Error::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn local_time_round_trip() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_615_734_566);
+        assert_eq!(decode(&encode(time).unwrap()).unwrap(), time);
+    }
+}
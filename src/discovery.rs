@@ -0,0 +1,110 @@
+//! # Discovery
+//!
+//! Discovery contains [`DiscoveredDevice`], which describes a Polar device found while scanning
+//! with [`crate::PolarSensor::scan`] or [`crate::PolarSensor::scan_filtered`].
+//!
+
+use btleplug::platform::Peripheral;
+
+/// A device found while scanning, along with the details parsed from its advertised name
+///
+/// Polar devices advertise names such as "Polar H10 8C4CAD2D" or "Polar Sense B5A3C2D1",
+/// made up of the brand, the model and the device id.
+#[derive(Debug, Clone)]
+pub struct DiscoveredDevice {
+    peripheral: Peripheral,
+    name: String,
+    model: String,
+    device_id: String,
+}
+
+impl DiscoveredDevice {
+    pub(crate) fn new(peripheral: Peripheral, name: String) -> DiscoveredDevice {
+        let (model, device_id) = parse_name(&name);
+
+        DiscoveredDevice {
+            peripheral,
+            model,
+            device_id,
+            name,
+        }
+    }
+
+    /// Get the underlying btleplug peripheral
+    pub fn peripheral(&self) -> &Peripheral {
+        &self.peripheral
+    }
+
+    /// Get the full advertised name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the model from the name (e.g, "H10", "OH1" or "Sense")
+    ///
+    /// Empty if the name doesn't contain a model.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Get the device id from the name (e.g, "8C4CAD2D")
+    ///
+    /// This is the id to pass to [`crate::PolarSensor::new`] to connect to this device.
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+}
+
+// Check if an advertised name belongs to the Polar device with `device_id`. The whole id has
+// to match, so an id that only ends with `device_id` doesn't count.
+pub(crate) fn name_matches(name: &str, device_id: &str) -> bool {
+    name.starts_with("Polar") && parse_name(name).1.eq_ignore_ascii_case(device_id)
+}
+
+// Split an advertised name into its model and device id, ignoring the "Polar" brand
+pub(crate) fn parse_name(name: &str) -> (String, String) {
+    let mut words: Vec<&str> = name.split_whitespace().collect();
+    if words.first() == Some(&"Polar") {
+        words.remove(0);
+    }
+
+    match words.split_last() {
+        Some((device_id, model)) => (model.join(" "), device_id.to_string()),
+        None => (String::new(), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        assert_eq!(
+            parse_name("Polar H10 8C4CAD2D"),
+            ("H10".to_string(), "8C4CAD2D".to_string())
+        );
+        assert_eq!(
+            parse_name("Polar Sense B5A3C2D1"),
+            ("Sense".to_string(), "B5A3C2D1".to_string())
+        );
+        assert_eq!(
+            parse_name("Polar Verity Sense B5A3C2D1"),
+            ("Verity Sense".to_string(), "B5A3C2D1".to_string())
+        );
+        assert_eq!(
+            parse_name("Polar 8C4CAD2D"),
+            (String::new(), "8C4CAD2D".to_string())
+        );
+        assert_eq!(parse_name("Polar"), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn match_names() {
+        assert!(name_matches("Polar H10 8C4CAD2D", "8C4CAD2D"));
+        assert!(name_matches("Polar OH1 8c4cad2d", "8C4CAD2D"));
+        assert!(!name_matches("Polar H10 A8C4CAD2D", "8C4CAD2D"));
+        assert!(!name_matches("Garmin 8C4CAD2D", "8C4CAD2D"));
+        assert!(!name_matches("Polar H10 8C4CAD2D", "4CAD2D"));
+    }
+}
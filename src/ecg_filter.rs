@@ -0,0 +1,207 @@
+//! # ECG filter
+//!
+//! ECG filter contains [`EcgFilter`], a chain of IIR [`Biquad`] sections for cleaning up raw
+//! ECG, such as removing baseline wander and mains interference.
+//!
+
+use crate::Ecg;
+
+use std::f64::consts::PI;
+
+/// A second order IIR filter section
+///
+/// Coefficients are normalized so `a0` is 1, and samples are processed in direct form II
+/// transposed. The constructors for common filters follow the Audio EQ Cookbook.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    /// Create a section from coefficients already divided by `a0`
+    pub fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Biquad {
+        Biquad {
+            b0,
+            b1,
+            b2,
+            a1,
+            a2,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Create a high-pass section removing content below `cutoff` Hz
+    pub fn high_pass(sample_rate: f64, cutoff: f64, q: f64) -> Biquad {
+        let (cos, alpha) = Biquad::angle(sample_rate, cutoff, q);
+        Biquad::normalized(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Create a low-pass section removing content above `cutoff` Hz
+    pub fn low_pass(sample_rate: f64, cutoff: f64, q: f64) -> Biquad {
+        let (cos, alpha) = Biquad::angle(sample_rate, cutoff, q);
+        Biquad::normalized(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Create a notch section removing `frequency` Hz, with a width set by `q`
+    pub fn notch(sample_rate: f64, frequency: f64, q: f64) -> Biquad {
+        let (cos, alpha) = Biquad::angle(sample_rate, frequency, q);
+        Biquad::normalized(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    // Cosine of the normalized angular frequency and the cookbook's alpha
+    fn angle(sample_rate: f64, frequency: f64, q: f64) -> (f64, f64) {
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+
+    fn normalized(b: [f64; 3], a: [f64; 3]) -> Biquad {
+        Biquad::new(
+            b[0] / a[0],
+            b[1] / a[0],
+            b[2] / a[0],
+            a[1] / a[0],
+            a[2] / a[0],
+        )
+    }
+
+    /// Filter the next sample
+    pub fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    /// Clear the filter state, as if no samples had been processed
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Filters ECG samples through a chain of [`Biquad`] sections
+///
+/// Samples go through each section in the order they were added. The filter keeps state
+/// between samples, so feed it one continuous stream at a fixed sample rate.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EcgFilter {
+    stages: Vec<Biquad>,
+}
+
+impl EcgFilter {
+    /// Create a filter with no sections, which passes samples through unchanged
+    pub fn new() -> EcgFilter {
+        EcgFilter::default()
+    }
+
+    /// Create the usual chain for ECG at `sample_rate` Hz with `mains` Hz (50 or 60) power
+    ///
+    /// A 0.5 Hz high-pass removes baseline wander, a notch removes mains interference and a
+    /// 40 Hz low-pass removes muscle noise. The notch is left out if `mains` is at or above
+    /// half the sample rate.
+    pub fn standard(sample_rate: u16, mains: u16) -> EcgFilter {
+        let rate = f64::from(sample_rate);
+        let butterworth = std::f64::consts::FRAC_1_SQRT_2;
+
+        let mut filter = EcgFilter::new().with_stage(Biquad::high_pass(rate, 0.5, butterworth));
+        if f64::from(mains) < rate / 2.0 {
+            filter = filter.with_stage(Biquad::notch(rate, f64::from(mains), 30.0));
+        }
+        filter.with_stage(Biquad::low_pass(
+            rate,
+            40.0_f64.min(rate / 2.5),
+            butterworth,
+        ))
+    }
+
+    /// Add a section to the end of the chain
+    pub fn with_stage(mut self, stage: Biquad) -> EcgFilter {
+        self.stages.push(stage);
+        self
+    }
+
+    /// Filter the next sample, returning the filtered value (in µV)
+    pub fn filter(&mut self, sample: &Ecg) -> f64 {
+        self.stages
+            .iter_mut()
+            .fold(f64::from(*sample.val()), |x, stage| stage.process(x))
+    }
+
+    /// Filter a run of samples, returning the filtered values (in µV)
+    pub fn filter_all(&mut self, samples: &[Ecg]) -> Vec<f64> {
+        samples.iter().map(|sample| self.filter(sample)).collect()
+    }
+
+    /// Clear the state of every section
+    pub fn reset(&mut self) {
+        self.stages.iter_mut().for_each(Biquad::reset);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sine(frequency: f64, rate: f64, count: usize) -> Vec<Ecg> {
+        (0..count)
+            .map(|i| {
+                let t = i as f64 / rate;
+                Ecg::from_val((1000.0 * (2.0 * PI * frequency * t).sin()) as i32)
+            })
+            .collect()
+    }
+
+    fn peak(values: &[f64]) -> f64 {
+        values.iter().fold(0.0, |max: f64, v| max.max(v.abs()))
+    }
+
+    #[test]
+    fn empty_filter() {
+        let mut filter = EcgFilter::new();
+        assert_eq!(filter.filter(&Ecg::from_val(-250)), -250.0);
+    }
+
+    #[test]
+    fn removes_baseline() {
+        let mut filter = EcgFilter::standard(130, 50);
+        let out = filter.filter_all(&vec![Ecg::from_val(5000); 1300]);
+
+        assert!(out[0] > 1000.0);
+        assert!(out[1299].abs() < 1.0);
+    }
+
+    #[test]
+    fn removes_mains() {
+        let mut filter = EcgFilter::new().with_stage(Biquad::notch(130.0, 50.0, 30.0));
+        let out = filter.filter_all(&sine(50.0, 130.0, 1300));
+        assert!(peak(&out[650..]) < 50.0);
+
+        // Frequencies away from the notch pass through
+        filter.reset();
+        let out = filter.filter_all(&sine(10.0, 130.0, 1300));
+        assert!(peak(&out[650..]) > 900.0);
+    }
+
+    #[test]
+    fn low_pass_keeps_dc() {
+        let mut stage = Biquad::low_pass(130.0, 40.0, std::f64::consts::FRAC_1_SQRT_2);
+        let out: Vec<f64> = (0..500).map(|_| stage.process(100.0)).collect();
+        assert!((out[499] - 100.0).abs() < 1e-6);
+    }
+}
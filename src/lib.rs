@@ -57,34 +57,82 @@
 #![deny(missing_docs)]
 
 pub use async_trait::async_trait;
-use btleplug::api::{Central, Characteristic, Manager as _, Peripheral as _, ScanFilter};
-use btleplug::platform::{Adapter, Manager, Peripheral};
-use futures::stream::StreamExt;
+use btleplug::api::{
+    BDAddr, Central, CentralEvent, Manager as _, Peripheral as _, PeripheralProperties, ScanFilter,
+    WriteType,
+};
+pub use btleplug::api::{Characteristic, ValueNotification};
+use btleplug::platform::{Adapter, Manager, Peripheral, PeripheralId};
+use futures::stream::{Stream, StreamExt};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
-use std::sync::Arc;
+use std::future::{self, Future};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
 use tokio::time::{self, Duration};
+pub use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
+mod bytes;
+mod config;
 mod control;
+mod device_time;
+mod discovery;
+mod ecg_filter;
+mod parse;
 mod polar_uuid;
+mod profile;
+mod quality;
 mod response;
+#[cfg(feature = "rpeak")]
+mod rpeak;
+mod session;
 
+pub use config::PolarConfig;
+use control::ble_error;
 pub use control::{
-    ControlPoint, ControlPointCommand, ControlPointResponseCode, ControlResponse, StreamSettings,
+    ControlPoint, ControlPointCommand, ControlPointResponseCode, ControlResponse, ControlWriteType,
+    ResponseCode, StreamSettings,
+};
+pub use discovery::DiscoveredDevice;
+pub use ecg_filter::{Biquad, EcgFilter};
+pub use parse::{
+    dispatch_notification, parse_control_response, parse_heart_rate, parse_pmd, parse_spo2,
+    parse_stream_settings, ParsedNotification,
 };
 use polar_uuid::{NotifyUuid, StringUuid};
-pub use response::{Acc, Ecg, HeartRate, PmdData, PmdRead};
+pub use profile::{DeviceProfile, H10Profile, PolarModel};
+pub use quality::{SignalQuality, SignalQualityEstimator};
+use response::SampleHistory;
+pub use response::{
+    Acc, AccCalibration, Axes3, Battery, Ecg, HeartRate, PmdData, PmdFrameType, PmdRead, Spo2,
+    TimestampCalibration, TimestampGap, TimestampTracker,
+};
+#[cfg(feature = "rpeak")]
+pub use rpeak::RPeakDetector;
+use session::SessionRecorder;
+pub use session::{ReplaySpeed, Session, SessionStats};
 
 /// Error type for general errors and Ble errors from btleplug
 #[derive(Debug)]
 pub enum Error {
     /// No bluetooth adapter found when trying to scan
     NoBleAdaptor,
-    /// Could not create control point link
+    /// The bluetooth adapter is present but powered off
+    AdapterOff,
+    /// Tried to connect while the device is already connected
+    AlreadyConnected,
+    /// Could not create control point link, or the device doesn't have a PMD control point
     NoControlPoint,
     /// Could not find a device when trying to connect
     NoDevice,
     /// Device is not connected, but function was called that requires it
+    ///
+    /// Also returned by [`PolarSensor::subscribe`] and [`PolarSensor::unsubscribe`] when
+    /// [`PolarSensor::connect`] hasn't been called yet.
     NotConnected,
     /// No measurement type selected
     NoDataType,
@@ -93,31 +141,64 @@ pub enum Error {
     /// Data packets received from device could not be parsed
     InvalidData,
     /// Not enough data was received
+    ///
+    /// Packets from the device that are cut short return [`Error::Parse`] instead, which says
+    /// which field was missing.
     InvalidLength,
+    /// A packet from the device was too short to hold one of its fields
+    Parse(ParseError),
     /// Command to write to PMD control point is Null
     NullCommand,
     /// Tried to create a struct using the wrong control point response
     WrongResponse,
     /// Tried to set a setting using with a `H10MeasurementType` that doesn't support that feature
     WrongType,
+    /// Nothing was received from the device in time
+    Timeout,
+    /// The control point used an op code this library doesn't know, which is held here
+    UnknownOpCode(u8),
+    /// More than one device matched the device id, holding the names of every match
+    AmbiguousDevice(Vec<String>),
+    /// A [`MeasurementControl`] request was made while the event loop wasn't running
+    LoopNotRunning,
+    /// The device rejected a GATT read or write with this ATT error, e.g. because it isn't paired
+    AttError(ResponseCode),
     /// An error occurred in the underlying BLE library
     BleError(btleplug::Error),
 }
 
+// btleplug errors don't implement `defmt::Format`, so this goes through `Display`
+#[cfg(feature = "defmt")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let msg = match self {
             Error::NoBleAdaptor => "No BLE adaptor".to_string(),
+            Error::AdapterOff => "Bluetooth adapter is powered off".to_string(),
+            Error::AlreadyConnected => "Already connected".to_string(),
             Error::NoControlPoint => "No control point".to_string(),
             Error::NoDevice => "No device".to_string(),
-            Error::NotConnected => "Not connected".to_string(),
+            Error::NotConnected => "Device is not connected".to_string(),
             Error::NoDataType => "No data type".to_string(),
             Error::CharacteristicNotFound => "Characteristic not found".to_string(),
             Error::InvalidData => "Invalid data".to_string(),
             Error::InvalidLength => "Invalid length".to_string(),
+            Error::Parse(why) => format!("Invalid length, {}", why),
             Error::NullCommand => "Null command".to_string(),
             Error::WrongResponse => "Wrong response".to_string(),
             Error::WrongType => "Wrong type".to_string(),
+            Error::Timeout => "Timed out".to_string(),
+            Error::UnknownOpCode(op) => format!("Unknown control point op code: {:#04x}", op),
+            Error::AmbiguousDevice(names) => {
+                format!("Several devices matched: {}", names.join(", "))
+            }
+            Error::LoopNotRunning => "Event loop is not running".to_string(),
+            Error::AttError(code) => format!("ATT error: {:?}", code),
             Error::BleError(er) => format!("BLE error: {:?}", er),
         };
         write!(f, "Arctic Error: {}", msg)
@@ -126,8 +207,77 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// Returns [`true`] for [`Error::InvalidLength`] and [`Error::Parse`]
+    ///
+    /// Short packets used to return [`Error::InvalidLength`], this matches both.
+    pub fn is_invalid_length(&self) -> bool {
+        matches!(self, Error::InvalidLength | Error::Parse(_))
+    }
+}
+
+/// Describes which field of a packet could not be read, and where in the packet it was
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    /// Name of the field being read (e.g, "timestamp")
+    pub context: &'static str,
+    /// Offset of the field from the start of the packet
+    pub offset: usize,
+    /// Number of bytes the field needed
+    pub len: usize,
+    /// Number of bytes left in the packet from `offset`
+    pub got: usize,
+}
+
+impl ParseError {
+    // Check `data` holds `len` bytes at `offset` for the field `context`
+    pub(crate) fn check(
+        context: &'static str,
+        data: &[u8],
+        offset: usize,
+        len: usize,
+    ) -> PolarResult<()> {
+        let got = data.len().saturating_sub(offset);
+        if got < len {
+            return Err(Error::Parse(ParseError {
+                context,
+                offset,
+                len,
+                got,
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: needed {} bytes at offset {}, got {}",
+            self.context, self.len, self.offset, self.got
+        )
+    }
+}
+
+// Times service discovery is run when connecting before settling for what was found
+const DISCOVERY_ATTEMPTS: usize = 3;
+// Time to wait between service discovery attempts
+const DISCOVERY_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// Times the event loop tries to reconnect to a dropped device before giving up
+const RECONNECT_ATTEMPTS: usize = 10;
+// Longest wait between reconnect attempts, the wait doubles after each one up to this
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// Largest notification payload the H10 sends, from its 232 byte MTU less the 3 byte ATT header
+const MAX_PACKET_SIZE: usize = 229;
+
 /// List of measurement types you can request
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum H10MeasurementType {
     /// Volts (V)
     Ecg,
@@ -148,23 +298,46 @@ impl TryFrom<u8> for H10MeasurementType {
 }
 
 impl H10MeasurementType {
-    fn as_u8(&self) -> u8 {
+    /// Get the byte the PMD service uses for this measurement type
+    pub fn as_u8(&self) -> u8 {
         match *self {
             H10MeasurementType::Ecg => 0x0,
             H10MeasurementType::Acc => 0x2,
         }
     }
 
-    fn as_bytes(&self) -> u8 {
-        match *self {
-            H10MeasurementType::Ecg => 3,
-            H10MeasurementType::Acc => 6,
+    // Size of one sample in bytes for the frame type of a PMD data packet, or `None` if the
+    // frame can't be decoded one sample at a time
+    fn as_bytes(&self, frame_type: PmdFrameType) -> Option<u8> {
+        match (*self, frame_type) {
+            (_, PmdFrameType::Delta(_)) => None,
+            (H10MeasurementType::Ecg, PmdFrameType::Raw(_)) => Some(3),
+            // Frame types 0, 1 and 2 hold three 8, 16 or 24 bit axes
+            (H10MeasurementType::Acc, PmdFrameType::Raw(0)) => Some(3),
+            (H10MeasurementType::Acc, PmdFrameType::Raw(1)) => Some(6),
+            (H10MeasurementType::Acc, PmdFrameType::Raw(2)) => Some(9),
+            (H10MeasurementType::Acc, PmdFrameType::Raw(_)) => None,
         }
     }
+
+    /// Number of samples in a full PMD data packet with samples of `resolution` bits
+    ///
+    /// ECG samples always take 3 bytes, and ACC samples take one to three bytes per axis
+    /// depending on `resolution`. The H10 fills each notification before sending it, so this
+    /// can be used to size buffers or estimate latency as `samples / sample rate`.
+    pub fn expected_samples_per_packet(&self, resolution: u8) -> usize {
+        let sample_size = match *self {
+            H10MeasurementType::Ecg => 3,
+            H10MeasurementType::Acc => 3 * usize::from(resolution.div_ceil(8).clamp(1, 3)),
+        };
+
+        // Packets start with the type, an 8 byte timestamp and the frame type
+        (MAX_PACKET_SIZE - 10) / sample_size
+    }
 }
 
 /// Struct that reads what features are available on your device
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct SupportedFeatures {
     /// Electrocardiogram
     pub ecg: bool,
@@ -193,6 +366,19 @@ impl SupportedFeatures {
             mag: (mes & 0b01000000) != 0,
         }
     }
+
+    /// Get the supported features that can be measured with [`H10MeasurementType`]
+    ///
+    /// Features without a measurement type (PPG, PPI, gyroscope and magnetometer) are left out.
+    pub fn available_types(&self) -> Vec<H10MeasurementType> {
+        [
+            (self.ecg, H10MeasurementType::Ecg),
+            (self.acc, H10MeasurementType::Acc),
+        ]
+        .into_iter()
+        .filter_map(|(supported, ty)| supported.then_some(ty))
+        .collect()
+    }
 }
 
 /// Trait for handling events coming from a device
@@ -203,6 +389,12 @@ pub trait EventHandler: Send + Sync {
     /// Contains the current battery level.
     async fn battery_update(&self, _battery_level: u8) {}
 
+    /// Dispatched along with [`EventHandler::battery_update`], with the charging state included
+    ///
+    /// The charging state is the one read when connecting, or by the last call to
+    /// [`PolarSensor::battery_status`], and is [`None`] if the device doesn't expose one.
+    async fn battery_status_update(&self, _ctx: &PolarSensor, _battery: Battery) {}
+
     /// Dispatched when a heart rate update is received
     ///
     /// Contains information about the heart rate and R-R timing
@@ -213,6 +405,34 @@ pub trait EventHandler: Send + Sync {
     /// Contains data in a [`PmdRead`]
     async fn measurement_update(&self, _ctx: &PolarSensor, _data: PmdRead) {}
 
+    /// Dispatched when a SpO2 update is received from a device with a Pulse Oximeter service
+    ///
+    /// Contains the oxygen saturation and pulse rate
+    async fn spo2_update(&self, _ctx: &PolarSensor, _spo2: Spo2) {}
+
+    /// Dispatched when a notification could not be parsed, the event loop skips it and carries on
+    ///
+    /// Contains the UUID the notification came from and its raw bytes
+    async fn on_parse_error(&self, _ctx: &PolarSensor, _uuid: Uuid, _data: &[u8]) {}
+
+    /// Dispatched when a PMD packet isn't newer than the last packet of its type, which happens
+    /// when a packet is repeated or arrives out of order
+    ///
+    /// Contains the newest timestamp seen before it and the packet itself. The packet is still
+    /// passed on afterwards unless [`PolarSensor::skip_out_of_order`] is set.
+    async fn on_out_of_order(&self, _ctx: &PolarSensor, _newest_time_stamp: u64, _data: &PmdRead) {}
+
+    /// Dispatched each time a window of ECG samples has been checked for signal quality
+    ///
+    /// Contains whether the electrodes are likely not touching the skin properly
+    async fn signal_quality(&self, _ctx: &PolarSensor, _quality: SignalQuality) {}
+
+    /// Dispatched when a [`RPeakDetector`] set with [`PolarSensor::rpeak_detector`] finds a beat
+    ///
+    /// Contains the RR interval (in ms) derived from the ECG stream
+    #[cfg(feature = "rpeak")]
+    async fn rr_detected(&self, _ctx: &PolarSensor, _rr: u16) {}
+
     /// Checked at start of each event loop
     ///
     /// Returns [`false`] if the event loop should terminate and close up
@@ -225,6 +445,7 @@ pub trait EventHandler: Send + Sync {
 pub type PolarResult<T> = std::result::Result<T, Error>;
 
 /// A list of stream types that can be subscribed to
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum NotifyStream {
     /// Receive battery updates
     Battery,
@@ -234,6 +455,10 @@ pub enum NotifyStream {
     MeasurementCP,
     /// Receive updates from the PMD data stream (acceleration or ECG)
     MeasurementData,
+    /// Receive SpO2 updates from the standard Pulse Oximeter service
+    ///
+    /// Only some optical devices have this, see [`PolarSensor::subscribe_if_available`].
+    Spo2,
 }
 
 impl From<NotifyStream> for Uuid {
@@ -242,6 +467,204 @@ impl From<NotifyStream> for Uuid {
     }
 }
 
+/// Device information read with [`PolarSensor::device_info`]
+///
+/// Fields are [`None`] when the device doesn't expose that characteristic.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DeviceInfo {
+    /// Model number (e.g, "H10")
+    pub model_number: Option<String>,
+    /// Manufacturer name
+    pub manufacturer_name: Option<String>,
+    /// Hardware revision
+    pub hardware_revision: Option<String>,
+    /// Firmware revision
+    pub firmware_revision: Option<String>,
+    /// Software revision
+    pub software_revision: Option<String>,
+    /// Serial number
+    pub serial_number: Option<String>,
+    /// Raw system ID
+    pub system_id: Option<Vec<u8>>,
+}
+
+/// Reference count for a subscription shared by several users
+#[derive(Debug, Default)]
+struct SubscriptionRefs {
+    /// Number of users currently holding the subscription
+    count: usize,
+    /// Whether the first user subscribed, rather than it being subscribed already
+    owned: bool,
+}
+
+/// Snapshot of a device's connection, taken with [`PolarSensor::status`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeviceStatus {
+    /// Whether the device is connected
+    pub connected: bool,
+    /// Signal strength of the last advertisement, if known
+    pub rssi: Option<i16>,
+    /// The advertised name (e.g, "Polar H10 8C4CAD2D")
+    pub local_name: Option<String>,
+    /// The bluetooth address of the device
+    pub address: String,
+    /// Measurements started through this sensor
+    pub active_measurements: Vec<H10MeasurementType>,
+}
+
+/// Cloneable handle to start and stop measurements from the running event loop
+///
+/// Get one with [`PolarSensor::measurement_control`] before starting the event loop. Requests
+/// are carried out by the event loop between notifications, so they never overlap with the
+/// measurements it starts and stops itself.
+#[derive(Debug, Clone)]
+pub struct MeasurementControl {
+    tx: mpsc::UnboundedSender<ControlRequest>,
+    running: Arc<Mutex<bool>>,
+}
+
+impl MeasurementControl {
+    /// Start measurement `ty` from the event loop
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LoopNotRunning`] if the event loop isn't running, otherwise the same
+    /// errors as [`PolarSensor::start`].
+    pub async fn start(&self, ty: H10MeasurementType) -> PolarResult<ControlResponse> {
+        self.request(ControlPointCommand::RequestMeasurementStart, ty)
+            .await
+    }
+
+    /// Stop measurement `ty` from the event loop
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LoopNotRunning`] if the event loop isn't running, otherwise the same
+    /// errors as [`PolarSensor::stop`].
+    pub async fn stop(&self, ty: H10MeasurementType) -> PolarResult<ControlResponse> {
+        self.request(ControlPointCommand::StopMeasurement, ty).await
+    }
+
+    async fn request(
+        &self,
+        command: ControlPointCommand,
+        ty: H10MeasurementType,
+    ) -> PolarResult<ControlResponse> {
+        let (reply, response) = oneshot::channel();
+        {
+            // The loop clears `running` before failing what's left in the channel, so nothing
+            // can be sent after it stops
+            let running = self.running.lock().expect("running lock poisoned");
+            if !*running {
+                return Err(Error::LoopNotRunning);
+            }
+            self.tx
+                .send(ControlRequest { command, ty, reply })
+                .map_err(|_| Error::LoopNotRunning)?;
+        }
+
+        response.await.map_err(|_| Error::LoopNotRunning)?
+    }
+}
+
+// A command sent from a `MeasurementControl` to the event loop
+#[derive(Debug)]
+struct ControlRequest {
+    command: ControlPointCommand,
+    ty: H10MeasurementType,
+    reply: oneshot::Sender<PolarResult<ControlResponse>>,
+}
+
+// Serves `MeasurementControl` requests while the event loop is dispatching. Dropping it fails
+// any requests it didn't get to, including when the loop is cancelled.
+struct ControlServer<'a> {
+    running: &'a Mutex<bool>,
+    requests: tokio::sync::MutexGuard<'a, mpsc::UnboundedReceiver<ControlRequest>>,
+}
+
+impl<'a> ControlServer<'a> {
+    async fn start(sensor: &'a PolarSensor) -> ControlServer<'a> {
+        let requests = sensor.control_rx.lock().await;
+        *sensor
+            .control_running
+            .lock()
+            .expect("running lock poisoned") = true;
+
+        ControlServer {
+            running: &sensor.control_running,
+            requests,
+        }
+    }
+}
+
+impl Drop for ControlServer<'_> {
+    fn drop(&mut self) {
+        *self.running.lock().expect("running lock poisoned") = false;
+        while let Ok(request) = self.requests.try_recv() {
+            let _ = request.reply.send(Err(Error::LoopNotRunning));
+        }
+    }
+}
+
+/// Connection priority to ask the bluetooth backend for, see
+/// [`PolarSensor::request_connection_priority`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectionPriority {
+    /// Short connection intervals for the lowest latency, using more battery
+    High,
+    /// The backend's default tradeoff between latency and battery
+    #[default]
+    Balanced,
+    /// Long connection intervals to save battery, adding latency
+    LowPower,
+}
+
+/// Connection transitions of a device, from [`PolarSensor::connection_events`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectionState {
+    /// The device connected
+    Connected,
+    /// The device disconnected
+    Disconnected,
+}
+
+/// Per-stream outcome of subscribing or unsubscribing to several streams at once
+#[derive(Debug)]
+pub struct BatchResult {
+    results: Vec<(NotifyStream, PolarResult<()>)>,
+}
+
+impl BatchResult {
+    /// Get the result for each stream, in the order they were requested
+    pub fn results(&self) -> &Vec<(NotifyStream, PolarResult<()>)> {
+        &self.results
+    }
+
+    /// Returns [`true`] if every stream succeeded
+    pub fn all_ok(&self) -> bool {
+        self.results.iter().all(|(_, res)| res.is_ok())
+    }
+
+    /// Get the streams that succeeded
+    pub fn succeeded(&self) -> Vec<NotifyStream> {
+        self.results
+            .iter()
+            .filter(|(_, res)| res.is_ok())
+            .map(|(stream, _)| *stream)
+            .collect()
+    }
+
+    /// Get the streams that failed along with their error
+    pub fn failed(&self) -> Vec<(NotifyStream, &Error)> {
+        self.results
+            .iter()
+            .filter_map(|(stream, res)| res.as_ref().err().map(|why| (*stream, why)))
+            .collect()
+    }
+}
+
 /// The core Polar device structure. Keeps track of connection and event dispatching.
 ///
 /// ## Example
@@ -263,6 +686,35 @@ impl From<NotifyStream> for Uuid {
 /// # }
 /// // Can now subscribe to events, set event handler, run the event loop, etc
 /// ```
+///
+/// ## Connection parameters
+///
+/// The negotiated BLE connection interval, peripheral latency and supervision timeout
+/// can't be read through [`PolarSensor`]. They are chosen by the operating system's
+/// bluetooth stack and btleplug doesn't expose them on any platform, so tools like
+/// `btmon` (Linux) or PacketLogger (macOS) are needed to inspect them.
+///
+/// ## Offline recordings
+///
+/// Recordings stored on the device can't be listed, downloaded or cleared through
+/// [`PolarSensor`]. They are only reachable through Polar's file transfer service, which
+/// this library doesn't implement, so the Polar Beat app or the official Polar SDK is
+/// needed to free up the device's storage.
+///
+/// ## Concurrency
+///
+/// [`PolarSensor`] is [`Send`] and [`Sync`], so it can be shared in an [`Arc`] between a task
+/// running the event loop and other tasks. Methods that change the connection, and most
+/// configuration setters, take `&mut self`, everything else takes `&self`. Settings meant to be
+/// changed while the event loop is running take `&self` as well, these are
+/// [`PolarSensor::reconfigure_acc`], [`PolarSensor::set_delivery_filter`] and
+/// [`PolarSensor::clear_delivery_filter`]. Control point exchanges (such as
+/// [`PolarSensor::start`] and [`PolarSensor::stop`]) are serialized internally, so a command and
+/// its response are never interleaved with another command, even while the event loop is running.
+/// The control point subscription they need is reference counted, so one command finishing
+/// never unsubscribes it from under another, or from under a subscription made with
+/// [`PolarSensor::subscribe`]. To start and stop measurements without racing the event loop's
+/// own start and stop, use a [`MeasurementControl`] from [`PolarSensor::measurement_control`].
 pub struct PolarSensor {
     /// The device id written on the device (e.g, "8C4CAD2D")
     device_id: String,
@@ -274,12 +726,71 @@ pub struct PolarSensor {
     event_handler: Option<Arc<dyn EventHandler>>,
     /// Control point accessor
     control_point: Option<ControlPoint>,
+    /// Held for the whole of a control point write and its response
+    control_lock: tokio::sync::Mutex<()>,
+    /// Users of the control point subscription
+    control_point_refs: tokio::sync::Mutex<SubscriptionRefs>,
     /// Current type of info gathered
     data_type: Option<Vec<H10MeasurementType>>,
     /// Range of 2G, 4G or 8G (only for ACC)
-    range: u8,
+    range: AtomicU8,
     /// Sample rate in hz
-    sample_rate: u8,
+    sample_rate: AtomicU8,
+    /// Resolution in bits (only for ACC)
+    resolution: u8,
+    /// Profile set with [`PolarSensor::device_profile`], used instead of [`H10Profile`]
+    device_profile: Option<Arc<dyn DeviceProfile>>,
+    /// Model in the advertised name of the connected device
+    advertised_model: Option<PolarModel>,
+    /// Resolution in bits ECG samples are read with
+    ecg_resolution: AtomicU8,
+    /// Correction applied to ACC samples as they're received
+    acc_calibration: AccCalibration,
+    /// Measurement types passed on to the handler, [`None`] passes on every type
+    delivery_filter: Mutex<Option<HashSet<H10MeasurementType>>>,
+    /// Factor and phase of the decimation applied before data reaches the handler
+    decimation: Mutex<HashMap<H10MeasurementType, (usize, usize)>>,
+    /// Measurements started through this sensor, with the sample rate the device reported
+    active_measurements: Mutex<HashMap<H10MeasurementType, Option<u16>>>,
+    /// How commands are written to the control point
+    write_type: ControlWriteType,
+    /// Sender cloned into every [`MeasurementControl`]
+    control_tx: mpsc::UnboundedSender<ControlRequest>,
+    /// Requests from [`MeasurementControl`] handles, served by the event loop
+    control_rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<ControlRequest>>,
+    /// Whether the event loop is serving [`MeasurementControl`] requests
+    control_running: Arc<Mutex<bool>>,
+    /// Bounded channel that measurement data is delivered to instead of the event handler
+    measurement_tx: Option<mpsc::Sender<PmdRead>>,
+    /// Number of measurement packets dropped because the channel was full
+    dropped_packets: AtomicUsize,
+    /// Number of notifications that could not be parsed
+    parse_errors: AtomicUsize,
+    /// Newest PMD packet timestamp seen for each measurement type
+    last_time_stamps: Mutex<HashMap<H10MeasurementType, u64>>,
+    /// Number of PMD packets that weren't newer than the one before them
+    out_of_order: AtomicUsize,
+    /// Whether out of order PMD packets are skipped instead of passed on
+    skip_out_of_order: bool,
+    /// Most recent samples received by the event loop
+    history: Mutex<SampleHistory>,
+    /// Whether any heart rate packet has had the RR interval flag set
+    rr_seen: AtomicBool,
+    /// Charging state last read from the battery power state characteristic
+    charging: Mutex<Option<bool>>,
+    /// Features read from the control point since connecting
+    features: Mutex<Option<SupportedFeatures>>,
+    /// Streams currently subscribed to
+    subscriptions: Mutex<HashSet<NotifyStream>>,
+    /// Signal quality estimation for ECG data
+    signal_quality: Mutex<SignalQualityEstimator>,
+    /// Detects beats in the ECG stream for [`EventHandler::rr_detected`]
+    #[cfg(feature = "rpeak")]
+    rpeak: Mutex<Option<RPeakDetector>>,
+    /// How long to scan for the device when connecting
+    scan_duration: Duration,
+    /// Reconnect in the event loop if the connection drops
+    auto_reconnect: bool,
 }
 
 impl PolarSensor {
@@ -287,67 +798,381 @@ impl PolarSensor {
     ///
     /// # Errors
     ///
-    /// Returns a [`Error::BleError`] if the bluetooth manager could not be created
+    /// Returns a [`Error::BleError`] if the bluetooth manager could not be created, or
+    /// [`Error::InvalidLength`] if the device id isn't 8 characters long
     pub async fn new(device_id: String) -> PolarResult<PolarSensor> {
         let ble_manager = Manager::new().await.map_err(Error::BleError)?;
 
+        PolarSensor::new_with_manager(device_id, ble_manager)
+    }
+
+    /// Creates a new [`PolarSensor`] using an existing bluetooth manager.
+    ///
+    /// Useful when connecting to several devices, since they can share one [`Manager`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLength`] if the device id isn't 8 characters long
+    pub fn new_with_manager(device_id: String, ble_manager: Manager) -> PolarResult<PolarSensor> {
         if device_id.len() != 8 {
             return Err(Error::InvalidLength);
         }
 
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+
         Ok(PolarSensor {
             device_id,
             ble_manager,
             ble_device: None,
             event_handler: None,
             control_point: None,
+            control_lock: tokio::sync::Mutex::new(()),
+            control_point_refs: tokio::sync::Mutex::new(SubscriptionRefs::default()),
             data_type: None,
-            range: 8,
-            sample_rate: 200,
+            range: AtomicU8::new(8),
+            sample_rate: AtomicU8::new(200),
+            resolution: 16,
+            device_profile: None,
+            advertised_model: None,
+            ecg_resolution: AtomicU8::new(14),
+            acc_calibration: AccCalibration::default(),
+            delivery_filter: Mutex::new(None),
+            decimation: Mutex::new(HashMap::new()),
+            active_measurements: Mutex::new(HashMap::new()),
+            write_type: ControlWriteType::default(),
+            measurement_tx: None,
+            control_tx,
+            control_rx: tokio::sync::Mutex::new(control_rx),
+            control_running: Arc::new(Mutex::new(false)),
+            dropped_packets: AtomicUsize::new(0),
+            parse_errors: AtomicUsize::new(0),
+            last_time_stamps: Mutex::new(HashMap::new()),
+            out_of_order: AtomicUsize::new(0),
+            skip_out_of_order: false,
+            history: Mutex::new(SampleHistory::default()),
+            rr_seen: AtomicBool::new(false),
+            charging: Mutex::new(None),
+            features: Mutex::new(None),
+            subscriptions: Mutex::new(HashSet::new()),
+            signal_quality: Mutex::new(SignalQualityEstimator::default()),
+            #[cfg(feature = "rpeak")]
+            rpeak: Mutex::new(None),
+            scan_duration: Duration::from_secs(2),
+            auto_reconnect: false,
         })
     }
 
+    /// Creates a new [`PolarSensor`] set up using a [`PolarConfig`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PolarSensor::new`], and [`Error::InvalidData`]
+    /// if the range or sample rate isn't valid while ACC is one of the data types
+    pub async fn with_config(config: PolarConfig) -> PolarResult<PolarSensor> {
+        let mut polar = PolarSensor::new(config.device_id).await?;
+
+        for ty in config.data_types {
+            polar.data_type_push(ty);
+        }
+
+        if polar
+            .data_type
+            .as_ref()
+            .is_some_and(|types| types.contains(&H10MeasurementType::Acc))
+        {
+            polar.range(config.range)?;
+            polar.sample_rate(config.sample_rate)?;
+        }
+
+        polar.scan_duration(config.scan_duration);
+        polar.auto_reconnect(config.auto_reconnect);
+
+        Ok(polar)
+    }
+
+    /// Returns whether any bluetooth adapters are available, without scanning.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Error::BleError`] if the adapters could not be listed
+    pub async fn has_adapter(&self) -> PolarResult<bool> {
+        let adapters = self.ble_manager.adapters().await.map_err(Error::BleError)?;
+
+        Ok(!adapters.is_empty())
+    }
+
+    /// Returns whether the first bluetooth adapter is powered on.
+    ///
+    /// btleplug can't read the power state directly, so this starts and immediately stops a
+    /// scan, which the adapter refuses while it's powered off (e.g, blocked with `rfkill`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoBleAdaptor`] if there are no bluetooth adapters, or a
+    /// [`Error::BleError`] if scanning failed for another reason
+    pub async fn adapter_powered(&self) -> PolarResult<bool> {
+        let adapters = self
+            .ble_manager
+            .adapters()
+            .await
+            .map_err(|_| Error::NoBleAdaptor)?;
+
+        match adapters.first() {
+            Some(central) => probe_adapter(central).await,
+            None => Err(Error::NoBleAdaptor),
+        }
+    }
+
     /// Finds and connects to the device id associated with this device instance.
     ///
+    /// Service discovery is retried a few times with a short delay if it finishes before the
+    /// heart rate service, or the PMD service on models that have one, shows up. This happens
+    /// now and then on BlueZ.
+    ///
     /// # Errors
     ///
     /// Returns a [`Error::BleError`] if:
     /// - Unable to get bluetooth adapters
     /// - Unable to scan for devices
     /// - Unable to discover services for a device
-    /// Also returns [`Error::NoBleAdaptor`] if there are no adapters available
-    /// Can also return [`Error::NotConnected`] if no device was found
+    ///
+    /// Also returns [`Error::NoBleAdaptor`] if there are no adapters available,
+    /// [`Error::AdapterOff`] if the adapter is powered off
+    /// Can also return [`Error::NotConnected`] if no device was found,
+    /// [`Error::AmbiguousDevice`] if several devices advertise the same device id
+    /// and [`Error::AlreadyConnected`] if the device is already connected
+    ///
+    /// # Cancellation
+    ///
+    /// This is cancellation safe. Dropping the future (e.g, with [`tokio::select!`] or
+    /// [`tokio::time::timeout`]) stops the scan right away, and the sensor is left
+    /// disconnected as before if the device wasn't connected yet.
     pub async fn connect(&mut self) -> PolarResult<()> {
         // get the first bluetooth adapter
-        let adapters_result = self.ble_manager.adapters().await.map_err(Error::BleError);
+        self.connect_with_adapter(0).await
+    }
+
+    /// Same as [`PolarSensor::connect`], but scans using the adapter at `index`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoBleAdaptor`] if there is no adapter at `index`, otherwise
+    /// the same errors as [`PolarSensor::connect`]
+    pub async fn connect_with_adapter(&mut self, index: usize) -> PolarResult<()> {
+        let adapters = self
+            .ble_manager
+            .adapters()
+            .await
+            .map_err(|_| Error::NoBleAdaptor)?;
+
+        match adapters.into_iter().nth(index) {
+            Some(central) => self.connect_to(&central).await,
+            None => Err(Error::NoBleAdaptor),
+        }
+    }
+
+    /// Same as [`PolarSensor::connect`], but scans using the adapter with the given identifier.
+    ///
+    /// The identifier is compared against the adapter info reported by btleplug
+    /// (e.g. "hci0" on Linux), either in full or by its first word.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoBleAdaptor`] if no adapter matches `name`, otherwise
+    /// the same errors as [`PolarSensor::connect`]
+    pub async fn connect_with_adapter_named(&mut self, name: &str) -> PolarResult<()> {
+        let adapters = self
+            .ble_manager
+            .adapters()
+            .await
+            .map_err(|_| Error::NoBleAdaptor)?;
 
-        if let Ok(adapters) = adapters_result {
-            if adapters.is_empty() {
-                return Err(Error::NoBleAdaptor);
+        for central in adapters {
+            let info = central.adapter_info().await.map_err(Error::BleError)?;
+
+            if adapter_matches(&info, name) {
+                return self.connect_to(&central).await;
             }
+        }
 
-            let central = adapters.into_iter().next().unwrap();
-            central
-                .start_scan(ScanFilter::default())
-                .await
-                .map_err(Error::BleError)?;
-            time::sleep(Duration::from_secs(2)).await;
+        Err(Error::NoBleAdaptor)
+    }
+
+    /// Scan every adapter for Polar devices for `duration`
+    ///
+    /// Same as calling [`PolarSensor::scan_filtered`] with the prefix "Polar".
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoBleAdaptor`] if there are no bluetooth adapters.
+    pub async fn scan(&self, duration: Duration) -> PolarResult<Vec<DiscoveredDevice>> {
+        self.scan_filtered("Polar", duration).await
+    }
+
+    /// Scan every adapter for `duration`, returning devices whose advertised name starts with `prefix`
+    ///
+    /// This finds any Polar device (H10, OH1, Verity Sense, ...), not just the one this
+    /// [`PolarSensor`] connects to, and doesn't need to be connected.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoBleAdaptor`] if there are no bluetooth adapters, or
+    /// [`Error::AdapterOff`] if an adapter is powered off.
+    pub async fn scan_filtered(
+        &self,
+        prefix: &str,
+        duration: Duration,
+    ) -> PolarResult<Vec<DiscoveredDevice>> {
+        let adapters = self
+            .ble_manager
+            .adapters()
+            .await
+            .map_err(|_| Error::NoBleAdaptor)?;
+        if adapters.is_empty() {
+            return Err(Error::NoBleAdaptor);
+        }
 
-            self.ble_device = self.find_device(&central).await;
+        let mut scans = vec![];
+        for central in &adapters {
+            scans.push(ScanGuard::start(central).await?);
+        }
+        time::sleep(duration).await;
 
-            if let Some(device) = &self.ble_device {
-                device.connect().await.map_err(Error::BleError)?;
-                device.discover_services().await.map_err(Error::BleError)?;
+        for scan in scans {
+            scan.stop().await?;
+        }
 
-                let controller = ControlPoint::new(device).await?;
-                self.control_point = Some(controller);
-                return Ok(());
+        let mut devices = vec![];
+        for central in &adapters {
+            for p in central.peripherals().await.map_err(Error::BleError)? {
+                let name = match p.properties().await.map_err(Error::BleError)? {
+                    Some(properties) => properties.local_name,
+                    None => None,
+                };
+                if let Some(name) = name.filter(|name| name.starts_with(prefix)) {
+                    devices.push(DiscoveredDevice::new(p, name));
+                }
             }
+        }
+
+        Ok(devices)
+    }
 
-            return Err(Error::NoDevice);
+    // Scan for and connect to the device using a specific adapter
+    async fn connect_to(&mut self, central: &Adapter) -> PolarResult<()> {
+        if self.is_connected().await {
+            return Err(Error::AlreadyConnected);
         }
 
-        Err(Error::NoBleAdaptor)
+        // A powered off adapter refuses to scan, so this fails before waiting out the scan
+        let scan = ScanGuard::start(central).await?;
+        time::sleep(self.scan_duration).await;
+
+        let found = self.find_device(central).await;
+        scan.stop().await?;
+        let found = found?;
+        if let Some((name, _)) = &found {
+            let model = discovery::parse_name(name).0;
+            self.advertised_model = Some(PolarModel::from(model.as_str()));
+        }
+        self.ble_device = found.map(|(_, p)| p);
+        *self.features.lock().expect("features lock poisoned") = None;
+        self.last_time_stamps
+            .lock()
+            .expect("timestamps lock poisoned")
+            .clear();
+        self.subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .clear();
+        *self.control_point_refs.get_mut() = SubscriptionRefs::default();
+
+        if let Some(device) = &self.ble_device {
+            device.connect().await.map_err(Error::BleError)?;
+            return self.discover_services().await;
+        }
+
+        Err(Error::NoDevice)
+    }
+
+    /// Discover the device's services again, picking up characteristics that were missing
+    ///
+    /// Useful after a firmware update, when characteristics the device has can still come back
+    /// as [`Error::CharacteristicNotFound`]. The PMD control point and cached
+    /// [`SupportedFeatures`] are looked up again afterwards, and subscriptions are kept.
+    ///
+    /// btleplug can't clear the platform's GATT cache, so this only helps as far as the
+    /// platform looks the services up again:
+    /// - BlueZ answers from its own cache, which is only cleared by removing the device (e.g.
+    ///   `bluetoothctl remove <address>`) and pairing again, or by setting `Cache = no` in the
+    ///   `[GATT]` section of `/etc/bluetooth/main.conf`
+    /// - Windows adds services it didn't know about, but keeps the ones it already has
+    /// - macOS discovers services when connecting, so this does nothing there
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if there's no device, [`Error::NotConnected`] if it isn't
+    /// connected, or [`Error::BleError`] if discovery fails.
+    pub async fn rediscover_services(&mut self) -> PolarResult<()> {
+        if !self.is_connected().await {
+            self.device().await?;
+            return Err(Error::NotConnected);
+        }
+
+        self.forget_services();
+        self.discover_services().await
+    }
+
+    // Forget what was read from the last discovery so it's read again, keeping subscriptions
+    fn forget_services(&mut self) {
+        *self.features.get_mut().expect("features lock poisoned") = None;
+    }
+
+    // Discover services and find the PMD control point
+    //
+    // BlueZ can finish discovery before every service is there, so discovery is run again while
+    // the heart rate characteristic, or the control point of a model known to have one, is
+    // missing.
+    async fn discover_services(&mut self) -> PolarResult<()> {
+        let device = self.device().await?;
+        let expects_pmd = self
+            .advertised_model
+            .as_ref()
+            .is_some_and(PolarModel::has_pmd);
+
+        let mut attempts = 0;
+        let control_point = loop {
+            device.discover_services().await.map_err(Error::BleError)?;
+            attempts += 1;
+
+            // Heart rate only devices (e.g. H9, H7) don't have a PMD control point
+            let control_point = match ControlPoint::new(device).await {
+                Ok(controller) => Some(controller),
+                Err(Error::CharacteristicNotFound) => None,
+                Err(why) => return Err(why),
+            };
+            let has_heart_rate = find_characteristic(device, NotifyUuid::HeartMeasurement.into())
+                .await
+                .is_ok();
+
+            let complete = has_heart_rate && (control_point.is_some() || !expects_pmd);
+            if complete || attempts == DISCOVERY_ATTEMPTS {
+                break control_point;
+            }
+            time::sleep(DISCOVERY_RETRY_DELAY).await;
+        };
+
+        self.control_point = control_point.map(|mut controller| {
+            controller.set_write_type(self.write_type);
+            controller
+        });
+
+        // Read once here so battery updates in the event loop don't wait on a GATT read
+        // A failed read leaves the state unknown, `battery_status` reads it again
+        let charging = self.charging().await.unwrap_or(None);
+        *self.charging.get_mut().expect("charging lock poisoned") = charging;
+
+        Ok(())
     }
 
     /// Subscribes to a notify event on the device. These events will be sent via the [`EventHandler`].
@@ -355,18 +1180,30 @@ impl PolarSensor {
     /// # Errors
     ///
     /// Will return:
-    /// - [`Error::NotConnected`] if the device is not currently connected
+    /// - [`Error::NotConnected`] if [`PolarSensor::connect`] hasn't been called or the device is
+    ///   not currently connected
     /// - [`Error::CharacteristicNotFound`] if a given notify type is not found on the device
+    /// - [`Error::AttError`] if the device rejected the subscription, e.g. because it isn't paired
     /// - [`Error::BleError`] if there is an error subscribing to the event
+    ///
+    /// Subscribing to a stream that is already subscribed to does nothing.
     pub async fn subscribe(&self, stream: NotifyStream) -> PolarResult<()> {
-        let device = self.device().await?;
+        // Without a device `connect` was never called, which is the same problem to the caller
+        let device = self.device().await.map_err(|_| Error::NotConnected)?;
 
         if let Ok(true) = device.is_connected().await {
+            if self.is_subscribed(stream) {
+                return Ok(());
+            }
+
             let characteristic = find_characteristic(device, stream.into()).await?;
-            return device
-                .subscribe(&characteristic)
-                .await
-                .map_err(Error::BleError);
+            device.subscribe(&characteristic).await.map_err(ble_error)?;
+
+            self.subscriptions
+                .lock()
+                .expect("subscriptions lock poisoned")
+                .insert(stream);
+            return Ok(());
         }
 
         Err(Error::NotConnected)
@@ -377,28 +1214,372 @@ impl PolarSensor {
     /// # Errors
     ///
     /// Will return:
-    /// - [`Error::NotConnected`] if the device isn't connected
+    /// - [`Error::NotConnected`] if [`PolarSensor::connect`] hasn't been called or the device
+    ///   isn't connected
     /// - [`Error::CharacteristicNotFound`] if the specified notify type isn't found on the device
     /// - [`Error::BleError`] if there is an error subscribing to the event from within BLE
+    ///
+    /// Unsubscribing from a stream that isn't subscribed to does nothing.
     pub async fn unsubscribe(&self, stream: NotifyStream) -> PolarResult<()> {
-        let device = self.device().await?;
+        let device = self.device().await.map_err(|_| Error::NotConnected)?;
 
         if let Ok(true) = device.is_connected().await {
-            let characteristic = find_characteristic(device, stream.into()).await?;
+            if !self.is_subscribed(stream) {
+                return Ok(());
+            }
 
-            return device
+            let characteristic = find_characteristic(device, stream.into()).await?;
+            device
                 .unsubscribe(&characteristic)
                 .await
-                .map_err(Error::BleError);
+                .map_err(ble_error)?;
+
+            self.subscriptions
+                .lock()
+                .expect("subscriptions lock poisoned")
+                .remove(&stream);
+            return Ok(());
         }
 
         Err(Error::NotConnected)
     }
 
-    /// Returns whether the device is currently connected or not
-    pub async fn is_connected(&self) -> bool {
-        if let Some(device) = &self.ble_device {
-            if let Ok(value) = device.is_connected().await {
+    /// Stop every running measurement and unsubscribe from every stream, keeping the connection
+    ///
+    /// Everything is attempted even if some of it fails, and the active measurements,
+    /// subscriptions and packet ordering state are cleared either way. Don't call this while
+    /// the event loop is running.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet, otherwise the first error
+    /// hit while stopping or unsubscribing.
+    pub async fn reset(&self) -> PolarResult<()> {
+        self.device().await?;
+        self.stop_everything().await
+    }
+
+    // Stop every measurement and unsubscribe every stream, clearing their state even on errors
+    async fn stop_everything(&self) -> PolarResult<()> {
+        let mut first_error = None;
+
+        for ty in self.active_measurements() {
+            if let Err(why) = self.stop(ty).await {
+                first_error.get_or_insert(why);
+            }
+        }
+
+        let streams: Vec<NotifyStream> = self
+            .subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .iter()
+            .copied()
+            .collect();
+        for stream in streams {
+            if let Err(why) = self.unsubscribe(stream).await {
+                first_error.get_or_insert(why);
+            }
+        }
+
+        self.active_measurements
+            .lock()
+            .expect("active measurements lock poisoned")
+            .clear();
+        self.subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .clear();
+        self.last_time_stamps
+            .lock()
+            .expect("timestamps lock poisoned")
+            .clear();
+
+        match first_error {
+            Some(why) => Err(why),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether a stream is currently subscribed to
+    pub fn is_subscribed(&self, stream: NotifyStream) -> bool {
+        self.subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .contains(&stream)
+    }
+
+    /// Returns whether the device has the characteristic for a stream, without subscribing
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet.
+    pub async fn has_stream(&self, stream: NotifyStream) -> PolarResult<bool> {
+        let device = self.device().await?;
+
+        Ok(has_stream_in(&device.characteristics(), stream))
+    }
+
+    /// Subscribes to a notify event only if the device has it, returning whether it subscribed
+    ///
+    /// Useful for streams only some devices have, such as [`NotifyStream::Spo2`], where this
+    /// does nothing on devices without it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet, or the same errors as
+    /// [`PolarSensor::subscribe`] if the device has the stream.
+    pub async fn subscribe_if_available(&self, stream: NotifyStream) -> PolarResult<bool> {
+        let device = self.device().await?;
+        self.subscribe_if_in(&device.characteristics(), stream)
+            .await
+    }
+
+    // Subscribe to `stream` if it's one of the device's `characteristics`
+    async fn subscribe_if_in(
+        &self,
+        characteristics: &BTreeSet<Characteristic>,
+        stream: NotifyStream,
+    ) -> PolarResult<bool> {
+        if !has_stream_in(characteristics, stream) {
+            return Ok(false);
+        }
+
+        self.subscribe(stream).await?;
+        Ok(true)
+    }
+
+    /// Subscribes to several notify events, continuing past any that fail.
+    ///
+    /// Returns a [`BatchResult`] with the outcome of each [`PolarSensor::subscribe`] call.
+    pub async fn subscribe_all(&self, streams: &[NotifyStream]) -> BatchResult {
+        let mut results = Vec::with_capacity(streams.len());
+
+        for stream in streams {
+            results.push((*stream, self.subscribe(*stream).await));
+        }
+
+        BatchResult { results }
+    }
+
+    /// Unsubscribes from several notify events, continuing past any that fail.
+    ///
+    /// Returns a [`BatchResult`] with the outcome of each [`PolarSensor::unsubscribe`] call.
+    pub async fn unsubscribe_all(&self, streams: &[NotifyStream]) -> BatchResult {
+        let mut results = Vec::with_capacity(streams.len());
+
+        for stream in streams {
+            results.push((*stream, self.unsubscribe(*stream).await));
+        }
+
+        BatchResult { results }
+    }
+
+    /// Returns whether the connected device has a PMD control point.
+    ///
+    /// Heart rate only devices only support [`NotifyStream::HeartRate`] and [`NotifyStream::Battery`].
+    pub fn has_pmd(&self) -> bool {
+        self.control_point.is_some()
+    }
+
+    /// Wait for the next valid heart rate notification, without running the event loop
+    ///
+    /// Subscribes to [`NotifyStream::HeartRate`] if needed, and unsubscribes again afterwards
+    /// if it wasn't subscribed before. Notifications that can't be parsed are skipped, and a
+    /// failure to unsubscribe afterwards is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if no heart rate is received within `timeout`.
+    pub async fn next_heart_rate(&self, timeout: Duration) -> PolarResult<HeartRate> {
+        let device = self.device().await?;
+        let was_subscribed = self.is_subscribed(NotifyStream::HeartRate);
+
+        // Take the stream before subscribing so the first notification can't be missed
+        let stream = device.notifications().await.map_err(ble_error)?;
+        self.subscribe(NotifyStream::HeartRate).await?;
+        let heart_rate = wait_heart_rate(stream, timeout).await;
+
+        // The reading is still good if unsubscribing fails
+        if !was_subscribed {
+            let _ = self.unsubscribe(NotifyStream::HeartRate).await;
+        }
+        heart_rate
+    }
+
+    /// Read the device's clock from the Current Time characteristic
+    ///
+    /// The Current Time characteristic holds local time, which is converted with the host's
+    /// time zone.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CharacteristicNotFound`] if the device doesn't expose the Current Time
+    /// characteristic, or [`Error::InvalidData`] if the value isn't a valid date and time,
+    /// including when the device reports the year as unknown.
+    pub async fn get_device_time(&self) -> PolarResult<SystemTime> {
+        device_time::decode(&self.read(StringUuid::CurrentTime.into()).await?)
+    }
+
+    /// Set the device's clock through the Current Time characteristic
+    ///
+    /// Useful to line up the device's timestamps and offline recordings with the host clock
+    /// before a session. The device is set to the host's local time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CharacteristicNotFound`] if the device doesn't expose the Current Time
+    /// characteristic, or [`Error::InvalidData`] if `time` is before 1970.
+    pub async fn set_device_time(&self, time: SystemTime) -> PolarResult<()> {
+        let data = device_time::encode(time)?;
+        let device = self.device().await?;
+        let characteristic = find_characteristic(device, StringUuid::CurrentTime.into()).await?;
+
+        device
+            .write(&characteristic, &data, WriteType::WithResponse)
+            .await
+            .map_err(ble_error)
+    }
+
+    /// Reset the energy expended total through the standard Heart Rate Control Point
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CharacteristicNotFound`] if the device doesn't have a Heart Rate
+    /// Control Point, which devices without energy expended support leave out.
+    pub async fn reset_energy_expended(&self) -> PolarResult<()> {
+        let device = self.device().await?;
+        let characteristic =
+            find_characteristic(device, StringUuid::HeartRateControlPoint.into()).await?;
+
+        // 0x01 is the only command the Heart Rate Control Point defines
+        device
+            .write(&characteristic, &[0x01], WriteType::WithResponse)
+            .await
+            .map_err(ble_error)
+    }
+
+    /// Read the device name from the standard Device Name characteristic
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CharacteristicNotFound`] if the device or platform doesn't expose the
+    /// Device Name characteristic, which some platforms keep to themselves.
+    pub async fn device_name(&self) -> PolarResult<String> {
+        self.read_string(StringUuid::DeviceName.into()).await
+    }
+
+    /// Write a new device name to the standard Device Name characteristic
+    ///
+    /// The name is what the device advertises after it restarts, so changing it can stop
+    /// [`PolarSensor::connect`] from finding the device by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLength`] if `name` is longer than 248 bytes,
+    /// [`Error::CharacteristicNotFound`] if the Device Name characteristic isn't exposed, or
+    /// [`Error::BleError`] if the device doesn't allow writing its name.
+    pub async fn set_device_name(&self, name: &str) -> PolarResult<()> {
+        let name = device_name_bytes(name)?;
+        let device = self.device().await?;
+        let characteristic = find_characteristic(device, StringUuid::DeviceName.into()).await?;
+
+        device
+            .write(&characteristic, name, WriteType::WithResponse)
+            .await
+            .map_err(ble_error)
+    }
+
+    /// Check if the device is in its charger, where it refuses to start measurements
+    ///
+    /// Probes the control point with a settings request and checks for
+    /// [`ControlPointResponseCode::DeviceInCharger`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoControlPoint`] if the device doesn't have a PMD control point.
+    pub async fn is_in_charger(&self) -> PolarResult<bool> {
+        self.controller().await?;
+        let response = self
+            .get_pmd_response(
+                ControlPointCommand::GetMeasurementSettings,
+                H10MeasurementType::Ecg,
+            )
+            .await?;
+
+        Ok(*response.status() == ControlPointResponseCode::DeviceInCharger)
+    }
+
+    /// Get the connection state, signal strength, name, address and active measurements at once
+    ///
+    /// The device properties are only read once, so every field comes from the same moment.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet.
+    pub async fn status(&self) -> PolarResult<DeviceStatus> {
+        let device = self.device().await?;
+        let connected = device.is_connected().await.map_err(Error::BleError)?;
+        let properties = device.properties().await.map_err(Error::BleError)?;
+
+        Ok(status_from(
+            connected,
+            properties,
+            device.address(),
+            self.active_measurements(),
+        ))
+    }
+
+    /// Ask the bluetooth backend to use `priority` for the connection
+    ///
+    /// Returns whether the backend applied it. None of the backends btleplug supports
+    /// (BlueZ, CoreBluetooth and WinRT) let the connection priority be chosen, so this is
+    /// currently a no-op that returns [`false`] once there is a device.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet.
+    pub async fn request_connection_priority(
+        &self,
+        priority: ConnectionPriority,
+    ) -> PolarResult<bool> {
+        self.device().await?;
+
+        // Nothing to forward `priority` to until a backend exposes it
+        let _ = priority;
+        Ok(false)
+    }
+
+    /// Get a stream of connection changes for the device, without polling
+    /// [`PolarSensor::is_connected`]
+    ///
+    /// Only changes after the stream is created are yielded. The stream keeps going across
+    /// reconnects to the same device.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet, or [`Error::BleError`] if
+    /// the adapter events couldn't be listened to.
+    pub async fn connection_events(
+        &self,
+    ) -> PolarResult<impl Stream<Item = ConnectionState> + Send> {
+        let id = self.device().await?.id();
+        let adapters = self.ble_manager.adapters().await.map_err(Error::BleError)?;
+
+        let mut events = Vec::with_capacity(adapters.len());
+        for central in adapters {
+            events.push(central.events().await.map_err(Error::BleError)?);
+        }
+
+        let changes = futures::stream::select_all(events)
+            .filter_map(|event| future::ready(connection_change(event)));
+        Ok(connection_states(changes, id))
+    }
+
+    /// Returns whether the device is currently connected or not
+    pub async fn is_connected(&self) -> bool {
+        if let Some(device) = &self.ble_device {
+            if let Ok(value) = device.is_connected().await {
                 return value;
             }
         }
@@ -407,17 +1588,86 @@ impl PolarSensor {
     }
 
     /// Returns the RSSI of your device and the H10, or None if you have no device
+    ///
+    /// Also returns None if the device doesn't answer within 5 seconds, see
+    /// [`PolarSensor::rssi_with_timeout`] to choose the timeout.
     pub async fn rssi(&self) -> Option<i16> {
-        let device = self.device().await.ok()?;
+        self.rssi_with_timeout(Duration::from_secs(5))
+            .await
+            .ok()
+            .flatten()
+    }
 
-        if let Ok(Some(prop)) = device.properties().await {
-            return prop.rssi;
-        }
+    /// Returns the RSSI of your device and the H10, giving up after `timeout`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if there's no device, or [`Error::Timeout`] if the
+    /// properties couldn't be read within `timeout`.
+    pub async fn rssi_with_timeout(&self, timeout: Duration) -> PolarResult<Option<i16>> {
+        let device = self.device().await?;
+
+        let properties = within(timeout, async {
+            device.properties().await.map_err(Error::BleError)
+        })
+        .await?;
+
+        Ok(properties.and_then(|prop| prop.rssi))
+    }
+
+    /// Checks the connection is alive by reading the battery level, giving up after 5 seconds
+    ///
+    /// Some platforms keep reporting a dead connection as connected, so this can be used by a
+    /// watchdog when [`PolarSensor::is_connected`] isn't enough. See
+    /// [`PolarSensor::ping_with_timeout`] to choose the timeout.
+    pub async fn ping(&self) -> PolarResult<()> {
+        self.ping_with_timeout(Duration::from_secs(5)).await
+    }
+
+    /// Checks the connection is alive by reading the battery level, giving up after `timeout`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if there's no device, or [`Error::Timeout`] if the
+    /// battery level couldn't be read within `timeout`.
+    pub async fn ping_with_timeout(&self, timeout: Duration) -> PolarResult<()> {
+        alive(timeout, self.read(NotifyUuid::BatteryLevel.into())).await
+    }
+
+    /// Read the battery level and charging state
+    ///
+    /// The charging state comes from the battery power state characteristic, and is [`None`]
+    /// on devices that don't expose it. It's also kept for [`EventHandler::battery_status_update`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if there's no device, or [`Error::Parse`] if the
+    /// battery level was empty.
+    pub async fn battery_status(&self) -> PolarResult<Battery> {
+        let (level, charging) =
+            futures::join!(self.read(NotifyUuid::BatteryLevel.into()), self.charging());
+
+        let level = level?;
+        ParseError::check("battery level", &level, 0, 1)?;
+        let charging = charging?;
+        *self.charging.lock().expect("charging lock poisoned") = charging;
 
-        None
+        Ok(Battery {
+            level: level[0],
+            charging,
+        })
+    }
+
+    /// Private helper to read the charging state from the device, if it has one
+    async fn charging(&self) -> PolarResult<Option<bool>> {
+        let state = optional(self.read(StringUuid::BatteryPowerState.into()).await)?;
+
+        Ok(state
+            .and_then(|state| state.first().copied())
+            .and_then(Battery::charging_from_power_state))
     }
 
-    /// Prints info about your H10
+    /// Prints info about your device, skipping anything the device doesn't expose
     /// - Model Number
     /// - Manufacturer Name
     /// - Hardware Revision
@@ -426,42 +1676,104 @@ impl PolarSensor {
     /// - Serial Number
     /// - System ID
     pub async fn info(&self) {
-        println!(
-            "Model Number: {:?}",
-            self.read_string(StringUuid::ModelNumber.into()).await
-        );
-        println!(
-            "Manufacturer Name: {:?}",
-            self.read_string(StringUuid::ManufacturerName.into()).await
-        );
-        println!(
-            "Hardware Revision: {:?}",
-            self.read_string(StringUuid::HardwareRevision.into()).await
-        );
-        println!(
-            "Firmware Revision: {:?}",
-            self.read_string(StringUuid::FirmwareRevision.into()).await
-        );
-        println!(
-            "Software Revision: {:?}",
-            self.read_string(StringUuid::SoftwareRevision.into()).await
-        );
-        println!(
-            "Serial Number: {:?}",
-            self.read_string(StringUuid::SerialNumber.into()).await
-        );
-        println!(
-            "System ID: {:?}",
-            self.read(StringUuid::SystemId.into()).await
+        let info = match self.device_info().await {
+            Ok(info) => info,
+            Err(why) => {
+                println!("Could not read device info: {:?}", why);
+                return;
+            }
+        };
+
+        let strings = [
+            ("Model Number", &info.model_number),
+            ("Manufacturer Name", &info.manufacturer_name),
+            ("Hardware Revision", &info.hardware_revision),
+            ("Firmware Revision", &info.firmware_revision),
+            ("Software Revision", &info.software_revision),
+            ("Serial Number", &info.serial_number),
+        ];
+        for (name, value) in strings {
+            if let Some(value) = value {
+                println!("{}: {}", name, value);
+            }
+        }
+        if let Some(system_id) = &info.system_id {
+            println!("System ID: {:?}", system_id);
+        }
+    }
+
+    /// Read the model number characteristic and classify it as a [`PolarModel`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if there's no device, or [`Error::CharacteristicNotFound`]
+    /// if it doesn't expose a model number.
+    pub async fn model(&self) -> PolarResult<PolarModel> {
+        let data = self.read(StringUuid::ModelNumber.into()).await?;
+
+        Ok(model_from(&data))
+    }
+
+    /// Read the device information characteristics
+    ///
+    /// Characteristics the device doesn't have are left as [`None`], since optical devices
+    /// (OH1, Verity Sense) don't expose everything an H10 does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if no device has been found yet, or [`Error::BleError`] if a
+    /// characteristic the device has couldn't be read.
+    pub async fn device_info(&self) -> PolarResult<DeviceInfo> {
+        // Every read is sent at once, so this takes about one round trip rather than seven
+        let (
+            model_number,
+            manufacturer_name,
+            hardware_revision,
+            firmware_revision,
+            software_revision,
+            serial_number,
+            system_id,
+        ) = futures::join!(
+            self.read_string(StringUuid::ModelNumber.into()),
+            self.read_string(StringUuid::ManufacturerName.into()),
+            self.read_string(StringUuid::HardwareRevision.into()),
+            self.read_string(StringUuid::FirmwareRevision.into()),
+            self.read_string(StringUuid::SoftwareRevision.into()),
+            self.read_string(StringUuid::SerialNumber.into()),
+            self.read(StringUuid::SystemId.into()),
         );
+
+        Ok(DeviceInfo {
+            model_number: optional(model_number)?,
+            manufacturer_name: optional(manufacturer_name)?,
+            hardware_revision: optional(hardware_revision)?,
+            firmware_revision: optional(firmware_revision)?,
+            software_revision: optional(software_revision)?,
+            serial_number: optional(serial_number)?,
+            system_id: optional(system_id)?,
+        })
+    }
+
+    /// Lists every characteristic discovered on the device, along with its service and properties.
+    ///
+    /// Useful for finding out what an unsupported device offers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoDevice`] if [`PolarSensor::connect`] hasn't found a device
+    pub async fn list_characteristics(&self) -> PolarResult<Vec<Characteristic>> {
+        let device = self.device().await?;
+
+        Ok(device.characteristics().into_iter().collect())
     }
 
-    /// Prints the body location of your device
+    /// Prints the body location of your device, if it has one
     pub async fn body_location(&self) {
-        println!(
-            "Body Location: {:?}",
-            self.read(StringUuid::BodyLocation.into()).await
-        );
+        match optional(self.read(StringUuid::BodyLocation.into()).await) {
+            Ok(Some(location)) => println!("Body Location: {:?}", location),
+            Ok(None) => println!("Body Location: not available"),
+            Err(why) => println!("Could not read body location: {:?}", why),
+        }
     }
 
     /// Start measurement stream for [`H10MeasurementType`]
@@ -471,43 +1783,13 @@ impl PolarSensor {
     /// - [`Error::NoControlPoint`] if you haven't set a controller
     async fn start_measurement(&self, ty: H10MeasurementType) -> PolarResult<()> {
         let controller = self.controller().await?;
-        let mut command = vec![0x02u8, ty.as_u8()];
-
-        // Add range and resolution characteristic for acceleration only
-        match ty {
-            H10MeasurementType::Acc => {
-                // Range
-                command.push(0x02);
-                command.push(0x01);
-                command.push(self.range);
-                command.push(0x00);
-
-                // Sample rate
-                command.push(0x00);
-                command.push(0x01);
-                command.push(self.sample_rate);
-                command.push(0x00);
-
-                // Resolution
-                command.push(0x01);
-                command.push(0x01);
-                command.push(0x10);
-                command.push(0x00);
-            }
-            H10MeasurementType::Ecg => {
-                // Sample rate
-                command.push(0x00);
-                command.push(0x01);
-                command.push(0x82);
-                command.push(0x00);
-
-                // Resolution
-                command.push(0x01);
-                command.push(0x01);
-                command.push(0x0e);
-                command.push(0x00);
-            }
-        }
+        let command = self.profile().start_command(
+            ty,
+            self.range.load(Ordering::Relaxed),
+            self.sample_rate.load(Ordering::Relaxed),
+            self.resolution,
+        );
+
         controller
             .send_command(self.device().await?, command)
             .await?;
@@ -523,7 +1805,7 @@ impl PolarSensor {
     async fn stop_measurement(&self, data_type: H10MeasurementType) -> PolarResult<()> {
         let controller = self.controller().await?;
         controller
-            .send_command(self.device().await?, [3, data_type.as_u8()].to_vec())
+            .send_command(self.device().await?, self.profile().stop_command(data_type))
             .await
     }
 
@@ -533,11 +1815,16 @@ impl PolarSensor {
 
         if let Some(types) = &self.data_type {
             for ty in types {
-                out.push(StreamSettings::new(
+                let settings = StreamSettings::new(
                     &self
                         .get_pmd_response(ControlPointCommand::GetMeasurementSettings, *ty)
                         .await?,
-                )?);
+                )?;
+                if *ty == H10MeasurementType::Ecg {
+                    self.ecg_resolution
+                        .store(settings.resolution(), Ordering::Relaxed);
+                }
+                out.push(settings);
             }
         } else {
             return Err(Error::NoDataType);
@@ -549,19 +1836,54 @@ impl PolarSensor {
     async fn internal_settings(&self, ty: H10MeasurementType) -> PolarResult<()> {
         let controller = self.controller().await?;
         controller
-            .send_command(self.device().await?, [1, ty.as_u8()].to_vec())
+            .send_command(self.device().await?, self.profile().settings_command(ty))
             .await
     }
 
     /// Request the SDK features from your H10
+    ///
+    /// The features are only read from the device once per connection,
+    /// use [`PolarSensor::refresh_features`] to read them again.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NoDevice`] if you haven't connected to a device yet
+    /// - [`Error::NoControlPoint`] if the device is connected but has no PMD control point,
+    ///   such as heart rate only devices, so it has no SDK features at all
     pub async fn features(&self) -> PolarResult<SupportedFeatures> {
-        if let Ok(controller) = self.controller().await {
-            if let Ok(device) = self.device().await {
-                return Ok(SupportedFeatures::new(controller.read(device).await?[1]));
-            }
-            return Err(Error::NoDevice);
+        if let Some(features) = *self.features.lock().expect("features lock poisoned") {
+            return Ok(features);
         }
-        Err(Error::NoControlPoint)
+
+        self.refresh_features().await
+    }
+
+    /// Read the SDK features from your H10, replacing the cached value
+    ///
+    /// Returns the same errors as [`PolarSensor::features`].
+    pub async fn refresh_features(&self) -> PolarResult<SupportedFeatures> {
+        let device = self.device().await?;
+        let controller = self.controller().await?;
+
+        let data = controller.read(device).await?;
+        ParseError::check("features", &data, 1, 1)?;
+        let features = SupportedFeatures::new(data[1]);
+
+        *self.features.lock().expect("features lock poisoned") = Some(features);
+        Ok(features)
+    }
+
+    /// Use `profile` to build control point commands instead of [`H10Profile`]
+    ///
+    /// Every model uses the H10's commands by default, so this is only needed for devices whose
+    /// commands differ.
+    pub fn device_profile(&mut self, profile: Arc<dyn DeviceProfile>) {
+        self.device_profile = Some(profile);
+    }
+
+    // The profile commands are built with
+    fn profile(&self) -> &dyn DeviceProfile {
+        self.device_profile.as_deref().unwrap_or(&H10Profile)
     }
 
     async fn controller(&self) -> PolarResult<&ControlPoint> {
@@ -584,41 +1906,198 @@ impl PolarSensor {
             .await
     }
 
-    /// Adds this data type to read from the your H10 (if not already added)
-    pub fn data_type_push(&mut self, data_type: H10MeasurementType) {
-        match &mut self.data_type {
-            Some(types) => {
-                if types.len() != 2 && types[0] != data_type {
-                    types.push(data_type);
-                }
-            }
-            None => {
-                self.data_type = Some(vec![data_type]);
-            }
-        };
+    /// Get the sample rate (in Hz) the device reported when `ty` was last started
+    ///
+    /// Returns [`None`] if the measurement isn't running or the device didn't report a rate
+    /// in its start response. The device may cap the rate set with [`PolarSensor::sample_rate`].
+    pub fn effective_sample_rate(&self, ty: H10MeasurementType) -> Option<u16> {
+        self.active_measurements
+            .lock()
+            .expect("active measurements lock poisoned")
+            .get(&ty)
+            .copied()
+            .flatten()
     }
 
-    /// Removes a data type
-    pub fn data_type_pop(&mut self, data_type: H10MeasurementType) {
-        if let Some(data) = &mut self.data_type {
-            data.retain(|x| *x != data_type);
-            if data.is_empty() {
-                self.data_type = None;
-            }
-        }
+    /// Get the measurements that were started through this sensor and not stopped yet
+    pub fn active_measurements(&self) -> Vec<H10MeasurementType> {
+        self.active_measurements
+            .lock()
+            .expect("active measurements lock poisoned")
+            .keys()
+            .copied()
+            .collect()
     }
 
-    /// Get data types
-    pub fn data_type(&self) -> &Option<Vec<H10MeasurementType>> {
-        &self.data_type
+    // Keep track of running streams and the sample rate reported by the device
+    fn record_response(
+        &self,
+        command: &ControlPointCommand,
+        ty: H10MeasurementType,
+        response: &ControlResponse,
+    ) {
+        let mut active = self
+            .active_measurements
+            .lock()
+            .expect("active measurements lock poisoned");
+
+        match command {
+            ControlPointCommand::RequestMeasurementStart
+                if *response.status() == ControlPointResponseCode::Success =>
+            {
+                active.insert(ty, response.sample_rate());
+            }
+            ControlPointCommand::StopMeasurement => {
+                active.remove(&ty);
+            }
+            _ => {}
+        }
+    }
+
+    /// Set the offsets and scale applied to ACC samples as they're received
+    ///
+    /// Defaults to [`AccCalibration::default`], which leaves samples unchanged.
+    pub fn acc_calibration(&mut self, calibration: AccCalibration) {
+        self.acc_calibration = calibration;
+    }
+
+    /// Set the resolution in bits that ECG samples are decoded with
+    ///
+    /// The H10 reports 14 bits and sends sign extended 24 bit values, but some firmware sends
+    /// 22 bit values that need sign extending. This is also set from the device by
+    /// [`PolarSensor::settings`] when ECG is one of the data types.
+    pub fn ecg_resolution(&mut self, resolution: u8) {
+        *self.ecg_resolution.get_mut() = resolution;
+    }
+
+    /// Only pass on every `factor`th sample of `ty` to the event handler or measurement channel
+    ///
+    /// This lowers the rate data is handled at without changing the rate the device measures at.
+    /// The timestamp of each packet is moved to the last sample that was kept, and packets left
+    /// without samples aren't passed on. Sample history and signal quality still see every sample.
+    /// A `factor` of 0 or 1 turns decimation off.
+    pub fn set_decimation(&mut self, ty: H10MeasurementType, factor: usize) {
+        let mut decimation = self.decimation.lock().expect("decimation lock poisoned");
+
+        if factor <= 1 {
+            decimation.remove(&ty);
+        } else {
+            decimation.insert(ty, (factor, 0));
+        }
+    }
+
+    /// Only pass on measurement data of the types in `types` to the event handler or measurement channel
+    ///
+    /// Data of other types is still measured and seen by the sample history and signal quality,
+    /// it just isn't passed on, so a stream can be muted without stopping it. This takes `&self`
+    /// so it can be changed while the event loop is running, e.g. from the `ctx` passed to
+    /// [`EventHandler::measurement_update`]. Use [`PolarSensor::clear_delivery_filter`] to pass on
+    /// every type again.
+    pub fn set_delivery_filter(&self, types: &[H10MeasurementType]) {
+        *self
+            .delivery_filter
+            .lock()
+            .expect("delivery filter lock poisoned") = Some(types.iter().copied().collect());
+    }
+
+    /// Pass on measurement data of every type, undoing [`PolarSensor::set_delivery_filter`]
+    pub fn clear_delivery_filter(&self) {
+        *self
+            .delivery_filter
+            .lock()
+            .expect("delivery filter lock poisoned") = None;
+    }
+
+    // Check the delivery filter lets data of type `ty` through
+    fn delivered(&self, ty: H10MeasurementType) -> bool {
+        self.delivery_filter
+            .lock()
+            .expect("delivery filter lock poisoned")
+            .as_ref()
+            .is_none_or(|types| types.contains(&ty))
+    }
+
+    // Decimate a packet as set with `set_decimation`, returning false if nothing is left of it
+    fn decimate(&self, read: &mut PmdRead) -> bool {
+        let ty = *read.data_type();
+        let rate = self.effective_sample_rate(ty).unwrap_or(match ty {
+            H10MeasurementType::Ecg => 130,
+            H10MeasurementType::Acc => u16::from(self.sample_rate.load(Ordering::Relaxed)),
+        });
+
+        if let Some((factor, phase)) = self
+            .decimation
+            .lock()
+            .expect("decimation lock poisoned")
+            .get_mut(&ty)
+        {
+            read.decimate(*factor, phase, rate);
+        }
+
+        !read.samples().is_empty()
+    }
+
+    /// Set how long to scan for the device when connecting (defaults to 2 seconds)
+    pub fn scan_duration(&mut self, duration: Duration) {
+        self.scan_duration = duration;
+    }
+
+    /// Set whether the event loop reconnects to the device if the connection drops.
+    ///
+    /// After reconnecting, previous subscriptions and measurements are restored. Reconnecting is
+    /// tried up to 10 times, waiting the scan duration after the first attempt and twice as long
+    /// after each one that follows (up to 30 seconds). If every attempt fails, the event loop
+    /// returns the error of the last one.
+    pub fn auto_reconnect(&mut self, enabled: bool) {
+        self.auto_reconnect = enabled;
+    }
+
+    /// Set how commands are written to the PMD control point
+    ///
+    /// Defaults to [`ControlWriteType::WithResponse`]. Some platforms only acknowledge
+    /// writes without response, in which case [`ControlWriteType::Fallback`] can be used.
+    pub fn control_write_type(&mut self, write_type: ControlWriteType) {
+        self.write_type = write_type;
+        if let Some(controller) = &mut self.control_point {
+            controller.set_write_type(write_type);
+        }
+    }
+
+    /// Adds this data type to read from the your H10 (if not already added)
+    pub fn data_type_push(&mut self, data_type: H10MeasurementType) {
+        match &mut self.data_type {
+            Some(types) => {
+                if types.len() != 2 && types[0] != data_type {
+                    types.push(data_type);
+                }
+            }
+            None => {
+                self.data_type = Some(vec![data_type]);
+            }
+        };
+    }
+
+    /// Removes a data type
+    pub fn data_type_pop(&mut self, data_type: H10MeasurementType) {
+        if let Some(data) = &mut self.data_type {
+            data.retain(|x| *x != data_type);
+            if data.is_empty() {
+                self.data_type = None;
+            }
+        }
+    }
+
+    /// Get data types
+    pub fn data_type(&self) -> &Option<Vec<H10MeasurementType>> {
+        &self.data_type
     }
 
     /// Set data range for acceleration data
     pub fn range(&mut self, range: u8) -> PolarResult<()> {
-        if range == 2 || range == 4 || range == 8 {
+        if valid_range(range) {
             if let Some(ty) = &self.data_type {
                 if ty.contains(&H10MeasurementType::Acc) {
-                    self.range = range;
+                    self.range.store(range, Ordering::Relaxed);
                     return Ok(());
                 }
 
@@ -633,10 +2112,10 @@ impl PolarSensor {
 
     /// Set sample rate
     pub fn sample_rate(&mut self, rate: u8) -> PolarResult<()> {
-        if rate == 25 || rate == 50 || rate == 100 || rate == 200 {
+        if valid_sample_rate(rate) {
             if let Some(ty) = &self.data_type {
                 if ty.contains(&H10MeasurementType::Acc) {
-                    self.sample_rate = rate;
+                    self.sample_rate.store(rate, Ordering::Relaxed);
                     return Ok(());
                 }
 
@@ -649,6 +2128,45 @@ impl PolarSensor {
         Err(Error::InvalidData)
     }
 
+    /// Set resolution for acceleration data (defaults to 16 bits)
+    ///
+    /// The resolution is checked against the ACC settings reported by the device,
+    /// so the device has to be connected.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidData`] if the device doesn't support `resolution`
+    /// - [`Error::WrongType`] or [`Error::NoDataType`] if ACC isn't a data type
+    /// - Any error from reading the settings of the device
+    pub async fn resolution(&mut self, resolution: u8) -> PolarResult<()> {
+        match &self.data_type {
+            Some(ty) if !ty.contains(&H10MeasurementType::Acc) => return Err(Error::WrongType),
+            None => return Err(Error::NoDataType),
+            _ => {}
+        }
+
+        let settings = StreamSettings::new(
+            &self
+                .get_pmd_response(
+                    ControlPointCommand::GetMeasurementSettings,
+                    H10MeasurementType::Acc,
+                )
+                .await?,
+        )?;
+
+        self.resolution_from(&settings, resolution)
+    }
+
+    // Set the ACC resolution if it's one of the resolutions in `settings`
+    fn resolution_from(&mut self, settings: &StreamSettings, resolution: u8) -> PolarResult<()> {
+        if !settings.supported_resolutions().contains(&resolution) {
+            return Err(Error::InvalidData);
+        }
+
+        self.resolution = resolution;
+        Ok(())
+    }
+
     async fn device(&self) -> PolarResult<&Peripheral> {
         if let Some(device) = &self.ble_device {
             return Ok(device);
@@ -661,7 +2179,7 @@ impl PolarSensor {
         let device = self.device().await?;
 
         if let Ok(char) = find_characteristic(device, uuid).await {
-            return device.read(&char).await.map_err(Error::BleError);
+            return device.read(&char).await.map_err(ble_error);
         }
 
         Err(Error::CharacteristicNotFound)
@@ -670,13 +2188,171 @@ impl PolarSensor {
     async fn read_string(&self, uuid: Uuid) -> PolarResult<String> {
         let data = self.read(uuid).await?;
 
-        let string = String::from_utf8_lossy(&data).into_owned();
-        Ok(string.trim_matches(char::from(0)).to_string())
+        Ok(string_from(&data))
+    }
+
+    /// Deliver measurement data through a bounded channel instead of [`EventHandler::measurement_update`].
+    ///
+    /// The event loop never waits on the channel. If the receiver falls behind and the channel
+    /// holds `capacity` packets, new packets are dropped and counted in [`PolarSensor::dropped_packets`].
+    pub fn measurement_channel(&mut self, capacity: usize) -> mpsc::Receiver<PmdRead> {
+        let (tx, rx) = mpsc::channel(capacity);
+        self.measurement_tx = Some(tx);
+        rx
+    }
+
+    /// Get a handle to start and stop measurements while the event loop is running
+    ///
+    /// Unlike [`PolarSensor::start`] and [`PolarSensor::stop`], the handle can be used from
+    /// other tasks while the event loop borrows this sensor, see [`MeasurementControl`].
+    pub fn measurement_control(&self) -> MeasurementControl {
+        MeasurementControl {
+            tx: self.control_tx.clone(),
+            running: self.control_running.clone(),
+        }
+    }
+
+    /// Get the number of measurement packets dropped because the channel receiver was too slow
+    pub fn dropped_packets(&self) -> usize {
+        self.dropped_packets.load(Ordering::Relaxed)
+    }
+
+    /// Keep the last `capacity` samples of each measurement type received by the event loop.
+    ///
+    /// The history can be read with [`PolarSensor::recent_acc`] and [`PolarSensor::recent_ecg`],
+    /// for example from the `ctx` passed to [`EventHandler::measurement_update`].
+    /// A capacity of 0 (the default) disables it.
+    pub fn sample_history(&mut self, capacity: usize) {
+        self.history
+            .lock()
+            .expect("history lock poisoned")
+            .set_capacity(capacity);
+    }
+
+    /// Get up to the last `n` acceleration samples, oldest first
+    pub fn recent_acc(&self, n: usize) -> Vec<Acc> {
+        self.history
+            .lock()
+            .expect("history lock poisoned")
+            .recent_acc(n)
+    }
+
+    /// Get up to the last `n` ECG samples, oldest first
+    pub fn recent_ecg(&self, n: usize) -> Vec<Ecg> {
+        self.history
+            .lock()
+            .expect("history lock poisoned")
+            .recent_ecg(n)
+    }
+
+    /// Returns whether any heart rate update so far has had the RR interval flag set.
+    ///
+    /// Devices that never set it likely don't support RR intervals at all.
+    pub fn rr_supported(&self) -> bool {
+        self.rr_seen.load(Ordering::Relaxed)
+    }
+
+    /// Detect beats in the ECG stream with `detector`, reporting RR intervals to
+    /// [`EventHandler::rr_detected`]
+    ///
+    /// The detector sees ECG samples before decimation.
+    #[cfg(feature = "rpeak")]
+    pub fn rpeak_detector(&mut self, detector: RPeakDetector) {
+        *self.rpeak.get_mut().expect("rpeak lock poisoned") = Some(detector);
+    }
+
+    /// Set how ECG signal quality is estimated for [`EventHandler::signal_quality`]
+    pub fn signal_quality_estimator(&mut self, estimator: SignalQualityEstimator) {
+        *self
+            .signal_quality
+            .lock()
+            .expect("signal quality lock poisoned") = estimator;
+    }
+
+    /// Get the number of notifications received by the event loop that could not be parsed
+    pub fn parse_error_count(&self) -> usize {
+        self.parse_errors.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of PMD packets the event loop received with a timestamp that wasn't newer
+    /// than the previous packet of the same type, i.e. repeated or reordered packets
+    pub fn out_of_order_count(&self) -> usize {
+        self.out_of_order.load(Ordering::Relaxed)
+    }
+
+    /// Skip out of order PMD packets instead of passing them on
+    ///
+    /// They're still counted and passed to [`EventHandler::on_out_of_order`], but don't reach
+    /// [`EventHandler::measurement_update`], the measurement channel or the sample history.
+    /// Off by default.
+    pub fn skip_out_of_order(&mut self, skip: bool) {
+        self.skip_out_of_order = skip;
+    }
+
+    // Check `read` is newer than the last packet of its type, returning the newest timestamp
+    // seen so far if it isn't
+    fn check_order(&self, read: &PmdRead) -> Option<u64> {
+        let mut last = self
+            .last_time_stamps
+            .lock()
+            .expect("timestamps lock poisoned");
+
+        match last.get(read.data_type()) {
+            Some(&newest) if read.time_stamp() <= newest => {
+                self.out_of_order.fetch_add(1, Ordering::Relaxed);
+                Some(newest)
+            }
+            _ => {
+                last.insert(*read.data_type(), read.time_stamp());
+                None
+            }
+        }
     }
 
     /// Sets an event handler with multiple methods for each possible event.
-    pub fn event_handler<H: EventHandler + 'static>(&mut self, event_handler: H) {
-        self.event_handler = Some(Arc::new(event_handler));
+    ///
+    /// Replaces any handler that was already set, returning it.
+    pub fn event_handler<H: EventHandler + 'static>(
+        &mut self,
+        event_handler: H,
+    ) -> Option<Arc<dyn EventHandler>> {
+        self.event_handler.replace(Arc::new(event_handler))
+    }
+
+    /// Removes the event handler, returning it if one was set
+    ///
+    /// A new handler needs to be set with [`PolarSensor::event_handler`] before running the
+    /// event loop again.
+    pub fn clear_event_handler(&mut self) -> Option<Arc<dyn EventHandler>> {
+        self.event_handler.take()
+    }
+
+    /// Change the ACC range and sample rate, restarting ACC with the new settings
+    ///
+    /// ACC is stopped and started again without any other control point command in between,
+    /// so this can be used while the event loop is running. Returns the device's response to
+    /// the new start command, check its [`ControlResponse::status`] to see if the device
+    /// accepted the settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidData`] if the range isn't 2, 4 or 8 or the sample rate isn't
+    /// 25, 50, 100 or 200.
+    pub async fn reconfigure_acc(&self, range: u8, rate: u8) -> PolarResult<ControlResponse> {
+        if !valid_range(range) || !valid_sample_rate(rate) {
+            return Err(Error::InvalidData);
+        }
+
+        let _guard = self.control_lock.lock().await;
+        let ty = H10MeasurementType::Acc;
+
+        // Stopping fails harmlessly if ACC wasn't running
+        self.pmd_exchange(ControlPointCommand::StopMeasurement, ty)
+            .await?;
+        self.range.store(range, Ordering::Relaxed);
+        self.sample_rate.store(rate, Ordering::Relaxed);
+        self.pmd_exchange(ControlPointCommand::RequestMeasurementStart, ty)
+            .await
     }
 
     // Function that listens for PMD responses and returns the response and stops listening
@@ -685,33 +2361,83 @@ impl PolarSensor {
         command: ControlPointCommand,
         ty: H10MeasurementType,
     ) -> PolarResult<ControlResponse> {
+        // Only one command can be in flight, otherwise responses can't be matched up
+        let _guard = self.control_lock.lock().await;
+        self.pmd_exchange(command, ty).await
+    }
+
+    // Write a command and wait for its response, `control_lock` must be held
+    async fn pmd_exchange(
+        &self,
+        command: ControlPointCommand,
+        ty: H10MeasurementType,
+    ) -> PolarResult<ControlResponse> {
+        let device = self.device().await?;
+
         // start measurement and capture response
-        let mut response: PolarResult<ControlResponse> = Err(Error::NoDevice);
-        if let Some(device) = &self.ble_device {
-            self.subscribe(NotifyStream::MeasurementCP).await?;
-            let mut notification_stream = device.notifications().await.map_err(Error::BleError)?;
-
-            // Execute write to PMD command point
-            match command {
-                ControlPointCommand::Null => return Err(Error::NullCommand),
-                ControlPointCommand::GetMeasurementSettings => self.internal_settings(ty).await?,
-                ControlPointCommand::RequestMeasurementStart => self.start_measurement(ty).await?,
-                ControlPointCommand::StopMeasurement => self.stop_measurement(ty).await?,
-            };
+        self.acquire_control_point().await?;
+        let response = self.write_and_wait(device, command, ty).await;
+        self.release_control_point().await?;
 
-            while let Some(data) = notification_stream.next().await {
-                if data.uuid == NotifyUuid::MeasurementCP.into() {
-                    response = Ok(ControlResponse::new(data.value)
-                        .await
-                        .expect("err value getting response"));
-                    break;
-                }
-            }
+        if let Ok(resp) = &response {
+            self.record_response(&command, ty, resp);
         }
-        self.unsubscribe(NotifyStream::MeasurementCP).await?;
         response
     }
 
+    // Write a command to the control point and wait for the response notification
+    async fn write_and_wait(
+        &self,
+        device: &Peripheral,
+        command: ControlPointCommand,
+        ty: H10MeasurementType,
+    ) -> PolarResult<ControlResponse> {
+        let notification_stream = device.notifications().await.map_err(Error::BleError)?;
+
+        // Execute write to PMD command point
+        match command {
+            ControlPointCommand::Null => return Err(Error::NullCommand),
+            ControlPointCommand::GetMeasurementSettings => self.internal_settings(ty).await?,
+            ControlPointCommand::RequestMeasurementStart => self.start_measurement(ty).await?,
+            ControlPointCommand::StopMeasurement => self.stop_measurement(ty).await?,
+        };
+
+        collect_response(notification_stream).await
+    }
+
+    // Take a reference on the control point subscription, subscribing if this is the first one
+    async fn acquire_control_point(&self) -> PolarResult<()> {
+        let mut refs = self.control_point_refs.lock().await;
+
+        if refs.count == 0 {
+            // The control point indicates its responses. btleplug subscribes to indications for
+            // characteristics that support them, and the OS stack confirms each one, so frames
+            // after the first aren't held up waiting on us.
+            //
+            // Leave a subscription made with `subscribe` alone when the last reference goes
+            refs.owned = !self.is_subscribed(NotifyStream::MeasurementCP);
+            if refs.owned {
+                self.subscribe(NotifyStream::MeasurementCP).await?;
+            }
+        }
+        refs.count += 1;
+
+        Ok(())
+    }
+
+    // Drop a reference on the control point subscription, unsubscribing after the last one
+    async fn release_control_point(&self) -> PolarResult<()> {
+        let mut refs = self.control_point_refs.lock().await;
+
+        refs.count = refs.count.saturating_sub(1);
+        if refs.count == 0 && refs.owned {
+            refs.owned = false;
+            self.unsubscribe(NotifyStream::MeasurementCP).await?;
+        }
+
+        Ok(())
+    }
+
     /// Run the internal event loop.
     ///
     /// This loop will receive all subscribed events and pass them on
@@ -725,56 +2451,195 @@ impl PolarSensor {
     /// add a measurement type. Subscribing to [`NotifyStream::MeasurementCP`] or [`NotifyStream::Battery`] only also can cause
     /// issues because they will send notifications rarely.
     pub async fn event_loop(&self) -> PolarResult<()> {
+        self.run_loop(self.handler(), future::pending()).await
+    }
+
+    /// Run the internal event loop until `token` is cancelled.
+    ///
+    /// Same as [`PolarSensor::event_loop`], but cancelling `token` stops measurements and
+    /// returns straight away, even if no notifications are arriving.
+    pub async fn event_loop_cancellable(&self, token: CancellationToken) -> PolarResult<()> {
+        self.run_loop(self.handler(), token.cancelled()).await
+    }
+
+    /// Run the internal event loop for at most `duration`.
+    ///
+    /// Same as [`PolarSensor::event_loop`], but once `duration` has passed the loop
+    /// stops measurements and returns, even if notifications are still arriving.
+    pub async fn event_loop_for(&self, duration: Duration) -> PolarResult<()> {
+        self.run_loop(self.handler(), time::sleep(duration)).await
+    }
+
+    /// Run the event loop for `duration` and return everything received as a [`Session`].
+    ///
+    /// This doesn't need an [`EventHandler`], any handler set with [`PolarSensor::event_handler`]
+    /// isn't called while recording. Measurement data sent to a [`PolarSensor::measurement_channel`]
+    /// isn't recorded.
+    pub async fn record_session(&self, duration: Duration) -> PolarResult<Session> {
+        let recorder = SessionRecorder::new();
+        self.run_loop(&recorder, time::sleep(duration)).await?;
+
+        Ok(recorder.finish())
+    }
+
+    fn handler(&self) -> &dyn EventHandler {
+        self.event_handler
+            .as_deref()
+            .expect("Arctic: Event loop requires an event handler.")
+    }
+
+    // Start measurements, dispatch device notifications to `eh` until done or `stop` completes,
+    // then stop measurements again
+    async fn run_loop(
+        &self,
+        eh: &dyn EventHandler,
+        stop: impl Future<Output = ()>,
+    ) -> PolarResult<()> {
+        self.run_until(self.device_loop(eh), stop).await
+    }
+
+    // Start measurements, run `events` until done or `stop` completes, then stop measurements
+    async fn run_until(
+        &self,
+        events: impl Future<Output = PolarResult<()>>,
+        stop: impl Future<Output = ()>,
+    ) -> PolarResult<()> {
+        self.start_loop_measurements().await?;
+
+        tokio::select! {
+            result = events => result?,
+            _ = stop => {}
+        }
+
+        self.stop_loop_measurements().await
+    }
+
+    // Dispatch notifications from the device, reconnecting if the connection drops
+    async fn device_loop(&self, eh: &dyn EventHandler) -> PolarResult<()> {
+        if let Some(device) = &self.ble_device {
+            loop {
+                let notification_stream = device.notifications().await.map_err(Error::BleError)?;
+                self.dispatch(eh, notification_stream).await?;
+
+                if !self.auto_reconnect || self.is_connected().await {
+                    break;
+                }
+                if !self.reconnect(eh, device).await? {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reconnect to a dropped device and restore subscriptions and measurements.
+    // Returns false if the event handler asked to stop before reconnecting.
+    async fn reconnect(&self, eh: &dyn EventHandler, device: &Peripheral) -> PolarResult<bool> {
+        let connect = || async {
+            device.connect().await.map_err(ble_error)?;
+            device.discover_services().await.map_err(ble_error)
+        };
+        if !self.retry_connect(eh, connect).await? {
+            return Ok(false);
+        }
+
+        let streams: Vec<NotifyStream> = self
+            .subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .iter()
+            .copied()
+            .collect();
+        for stream in streams {
+            let characteristic = find_characteristic(device, stream.into()).await?;
+            device.subscribe(&characteristic).await.map_err(ble_error)?;
+        }
+
+        // The device forgets running measurements when disconnected
+        self.active_measurements
+            .lock()
+            .expect("active measurements lock poisoned")
+            .clear();
+        self.start_loop_measurements().await?;
+
+        Ok(true)
+    }
+
+    // Run `connect` until it succeeds, backing off between attempts. Returns false if the event
+    // handler asked to stop first, or the last error once every attempt failed.
+    async fn retry_connect<F, Fut>(
+        &self,
+        eh: &dyn EventHandler,
+        mut connect: F,
+    ) -> PolarResult<bool>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = PolarResult<()>>,
+    {
+        let mut delay = self.scan_duration;
+        let mut attempts = 0;
+
+        loop {
+            if !eh.should_continue().await {
+                return Ok(false);
+            }
+
+            match connect().await {
+                Ok(()) => return Ok(true),
+                Err(why) => {
+                    attempts += 1;
+                    if attempts == RECONNECT_ATTEMPTS {
+                        return Err(why);
+                    }
+                }
+            }
+
+            time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    // Start every measurement in `self.data_type` before running the event loop
+    async fn start_loop_measurements(&self) -> PolarResult<()> {
+        // Heart rate only devices have no measurements to start or stop
+        if !self.has_pmd() {
+            return Ok(());
+        }
+
         // Stop any previous measurements that might not have been stopped properly
-        let _ = self
-            .get_pmd_response(
-                ControlPointCommand::StopMeasurement,
-                H10MeasurementType::Acc,
-            )
-            .await?;
-        let _ = self
-            .get_pmd_response(
-                ControlPointCommand::StopMeasurement,
-                H10MeasurementType::Ecg,
-            )
-            .await?;
+        for ty in self.active_measurements() {
+            let _ = self
+                .get_pmd_response(ControlPointCommand::StopMeasurement, ty)
+                .await?;
+        }
 
         // Start measurements
         if let Some(types) = &self.data_type {
             for ty in types {
-                let _ = self
+                let response = self
                     .get_pmd_response(ControlPointCommand::RequestMeasurementStart, *ty)
                     .await?;
+
+                // Left running by someone else, restart it so our settings are used
+                if *response.status() == ControlPointResponseCode::AlreadyInState {
+                    let _ = self
+                        .get_pmd_response(ControlPointCommand::StopMeasurement, *ty)
+                        .await?;
+                    let _ = self
+                        .get_pmd_response(ControlPointCommand::RequestMeasurementStart, *ty)
+                        .await?;
+                }
             }
         }
 
-        let eh = &self
-            .event_handler
-            .as_ref()
-            .expect("Arctic: Event loop requires an event handler.");
+        Ok(())
+    }
 
-        if let Some(device) = &self.ble_device {
-            let mut notification_stream = device.notifications().await.map_err(Error::BleError)?;
-            // Process while the BLE connection is not broken or stopped.
-            while let Some(data) = notification_stream.next().await {
-                if eh.should_continue().await {
-                    if data.uuid == NotifyUuid::BatteryLevel.into() {
-                        let battery = data.value[0];
-                        eh.battery_update(battery).await;
-                    } else if data.uuid == NotifyUuid::HeartMeasurement.into() {
-                        let hr = HeartRate::new(data.value)?;
-                        eh.heart_rate_update(self, hr).await;
-                    } else if data.uuid == NotifyUuid::MeasurementData.into() {
-                        if let Ok(response) = PmdRead::new(data.value) {
-                            eh.measurement_update(self, response).await;
-                        } else {
-                            eprintln!("Invalid data received from PMD data stream.");
-                        }
-                    }
-                } else {
-                    break;
-                }
-            }
+    // Stop every measurement in `self.data_type` after the event loop ends
+    async fn stop_loop_measurements(&self) -> PolarResult<()> {
+        if !self.has_pmd() {
+            return Ok(());
         }
 
         if let Some(types) = &self.data_type {
@@ -787,34 +2652,434 @@ impl PolarSensor {
         Ok(())
     }
 
-    async fn find_device(&self, central: &Adapter) -> Option<Peripheral> {
-        for p in central.peripherals().await.unwrap() {
-            if p.properties()
-                .await
-                .unwrap()
-                .unwrap()
-                .local_name
-                .iter()
-                .any(|name| name.starts_with("Polar") && name.ends_with(&self.device_id))
-            {
-                return Some(p);
-            }
-        }
+    /// Dispatch notifications from a user-supplied stream to the [`EventHandler`].
+    ///
+    /// Runs until the stream ends or [`EventHandler::should_continue`] returns [`false`].
+    /// Unlike [`PolarSensor::event_loop`], this does not start or stop any measurements, so it
+    /// can be used to replay recorded notifications or share a stream from an existing connection.
+    pub async fn event_loop_with<S>(&self, stream: S) -> PolarResult<()>
+    where
+        S: Stream<Item = ValueNotification>,
+    {
+        self.dispatch(self.handler(), stream).await
+    }
 
-        None
+    // Count a notification that couldn't be parsed and pass it on to `eh`, the loop carries on
+    async fn report_parse_error(&self, eh: &dyn EventHandler, data: &ValueNotification) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+        eh.on_parse_error(self, data.uuid, &data.value).await;
     }
-}
 
-/// Private helper to find characteristics from a [`Uuid`]
-async fn find_characteristic(device: &Peripheral, uuid: Uuid) -> PolarResult<Characteristic> {
-    device
-        .characteristics()
+    // Pass every notification of `stream` on to `eh`
+    async fn dispatch<S>(&self, eh: &dyn EventHandler, stream: S) -> PolarResult<()>
+    where
+        S: Stream<Item = ValueNotification>,
+    {
+        let mut server = ControlServer::start(self).await;
+        futures::pin_mut!(stream);
+        // Process while the BLE connection is not broken or stopped.
+        loop {
+            let data = tokio::select! {
+                data = stream.next() => match data {
+                    Some(data) => data,
+                    None => break,
+                },
+                Some(request) = server.requests.recv() => {
+                    let response = self.get_pmd_response(request.command, request.ty).await;
+                    let _ = request.reply.send(response);
+                    continue;
+                }
+            };
+
+            if eh.should_continue().await {
+                if data.uuid == NotifyUuid::BatteryLevel.into() {
+                    match data.value.first() {
+                        Some(level) => {
+                            eh.battery_update(*level).await;
+                            let battery = Battery {
+                                level: *level,
+                                charging: *self.charging.lock().expect("charging lock poisoned"),
+                            };
+                            eh.battery_status_update(self, battery).await;
+                        }
+                        None => self.report_parse_error(eh, &data).await,
+                    }
+                } else if data.uuid == NotifyUuid::HeartMeasurement.into() {
+                    match HeartRate::try_from(data.value.as_slice()) {
+                        Ok(hr) => {
+                            if hr.rr_present_flag() {
+                                self.rr_seen.store(true, Ordering::Relaxed);
+                            }
+                            eh.heart_rate_update(self, hr).await;
+                        }
+                        Err(_) => self.report_parse_error(eh, &data).await,
+                    }
+                } else if data.uuid == NotifyUuid::Spo2.into() {
+                    match Spo2::try_from(data.value.as_slice()) {
+                        Ok(spo2) => eh.spo2_update(self, spo2).await,
+                        Err(_) => self.report_parse_error(eh, &data).await,
+                    }
+                } else if data.uuid == NotifyUuid::MeasurementData.into() {
+                    if let Ok(mut response) = PmdRead::with_ecg_resolution(
+                        &data.value,
+                        self.ecg_resolution.load(Ordering::Relaxed),
+                    ) {
+                        if let Some(newest) = self.check_order(&response) {
+                            eh.on_out_of_order(self, newest, &response).await;
+                            if self.skip_out_of_order {
+                                continue;
+                            }
+                        }
+
+                        response.calibrate(&self.acc_calibration);
+
+                        self.history
+                            .lock()
+                            .expect("history lock poisoned")
+                            .push(&response);
+
+                        for quality in self.estimate_quality(&response) {
+                            eh.signal_quality(self, quality).await;
+                        }
+
+                        #[cfg(feature = "rpeak")]
+                        for rr in self.detect_rr(&response) {
+                            eh.rr_detected(self, rr).await;
+                        }
+
+                        if !self.decimate(&mut response) || !self.delivered(*response.data_type()) {
+                            continue;
+                        }
+
+                        match &self.measurement_tx {
+                            Some(tx) => {
+                                if let Err(TrySendError::Full(_)) = tx.try_send(response) {
+                                    self.dropped_packets.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                            None => eh.measurement_update(self, response).await,
+                        }
+                    } else {
+                        self.report_parse_error(eh, &data).await;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Feed ECG samples to the signal quality estimator, returning any finished windows
+    fn estimate_quality(&self, read: &PmdRead) -> Vec<SignalQuality> {
+        let mut estimator = self
+            .signal_quality
+            .lock()
+            .expect("signal quality lock poisoned");
+
+        read.samples()
+            .iter()
+            .filter_map(|sample| match sample {
+                PmdData::Ecg(ecg) => estimator.push(*ecg.val()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Feed ECG samples to the R-peak detector, returning any RR intervals found
+    #[cfg(feature = "rpeak")]
+    fn detect_rr(&self, read: &PmdRead) -> Vec<u16> {
+        let mut detector = self.rpeak.lock().expect("rpeak lock poisoned");
+        let Some(detector) = detector.as_mut() else {
+            return vec![];
+        };
+
+        read.samples()
+            .iter()
+            .filter_map(|sample| match sample {
+                PmdData::Ecg(ecg) => detector.push(f64::from(*ecg.val())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Find the peripheral advertising this device id, refusing to pick between several
+    async fn find_device(&self, central: &Adapter) -> PolarResult<Option<(String, Peripheral)>> {
+        let mut found = vec![];
+        for p in central.peripherals().await.map_err(Error::BleError)? {
+            let properties = p.properties().await.map_err(Error::BleError)?;
+            if let Some(name) = properties.and_then(|prop| prop.local_name) {
+                if discovery::name_matches(&name, &self.device_id) {
+                    found.push((name, p));
+                }
+            }
+        }
+
+        if found.len() > 1 {
+            return Err(Error::AmbiguousDevice(
+                found.into_iter().map(|(name, _)| name).collect(),
+            ));
+        }
+
+        Ok(found.pop())
+    }
+}
+
+/// Private helper to check if adapter info matches a user given identifier
+fn adapter_matches(info: &str, name: &str) -> bool {
+    info == name || info.split_whitespace().next() == Some(name)
+}
+
+/// Private helper to wait for the first valid heart rate in a notification stream
+async fn wait_heart_rate<S>(stream: S, timeout: Duration) -> PolarResult<HeartRate>
+where
+    S: Stream<Item = ValueNotification>,
+{
+    futures::pin_mut!(stream);
+    let first = async {
+        while let Some(data) = stream.next().await {
+            if data.uuid == NotifyUuid::HeartMeasurement.into() {
+                if let Ok(heart_rate) = HeartRate::new(data.value) {
+                    return Ok(heart_rate);
+                }
+            }
+        }
+
+        Err(Error::NotConnected)
+    };
+
+    time::timeout(timeout, first)
+        .await
+        .map_err(|_| Error::Timeout)?
+}
+
+/// Private helper to read a control point response from a notification stream, joining the
+/// parameters of every frame when the response is split over several
+async fn collect_response<S>(stream: S) -> PolarResult<ControlResponse>
+where
+    S: Stream<Item = ValueNotification>,
+{
+    futures::pin_mut!(stream);
+    let mut response: Option<ControlResponse> = None;
+
+    while let Some(data) = stream.next().await {
+        if data.uuid != NotifyUuid::MeasurementCP.into() {
+            continue;
+        }
+
+        let frame = ControlResponse::try_from(data.value.as_slice())?;
+        let response = match response.as_mut() {
+            Some(response) => {
+                response.append_frame(frame);
+                response
+            }
+            None => response.insert(frame),
+        };
+        if !response.has_more_frames() {
+            break;
+        }
+    }
+
+    // A stream ending before the last frame means the device went away
+    match response {
+        Some(response) if !response.has_more_frames() => Ok(response),
+        _ => Err(Error::NoDevice),
+    }
+}
+
+/// Private guard for a running scan, which stops the scan if it's dropped before [`ScanGuard::stop`]
+///
+/// This stops the adapter from scanning on when a connect or scan future is cancelled.
+struct ScanGuard {
+    central: Option<Adapter>,
+}
+
+impl ScanGuard {
+    async fn start(central: &Adapter) -> PolarResult<ScanGuard> {
+        central
+            .start_scan(ScanFilter::default())
+            .await
+            .map_err(scan_error)?;
+
+        Ok(ScanGuard {
+            central: Some(central.clone()),
+        })
+    }
+
+    async fn stop(mut self) -> PolarResult<()> {
+        match self.central.take() {
+            Some(central) => central.stop_scan().await.map_err(Error::BleError),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        // Drop can't await, so hand the stop off to the runtime if there still is one
+        if let (Some(central), Ok(handle)) =
+            (self.central.take(), tokio::runtime::Handle::try_current())
+        {
+            handle.spawn(async move {
+                let _ = central.stop_scan().await;
+            });
+        }
+    }
+}
+
+/// Private helper to check if an adapter is powered by starting and stopping a scan
+async fn probe_adapter(central: &Adapter) -> PolarResult<bool> {
+    match central.start_scan(ScanFilter::default()).await {
+        Ok(()) => {
+            central.stop_scan().await.map_err(Error::BleError)?;
+            Ok(true)
+        }
+        Err(why) if powered_off(&why) => Ok(false),
+        Err(why) => Err(Error::BleError(why)),
+    }
+}
+
+/// Private helper to report a failed scan as [`Error::AdapterOff`] when the adapter is powered off
+fn scan_error(error: btleplug::Error) -> Error {
+    if powered_off(&error) {
+        return Error::AdapterOff;
+    }
+
+    Error::BleError(error)
+}
+
+/// Private helper to recognise the errors backends give when scanning on a powered off adapter
+fn powered_off(error: &btleplug::Error) -> bool {
+    // BlueZ refuses with org.bluez.Error.NotReady, other backends mention the power state
+    let message = format!("{:?}", error).to_lowercase();
+    ["notready", "not ready", "powered off", "poweredoff"]
+        .iter()
+        .any(|hint| message.contains(hint))
+}
+
+/// Private helper to turn a missing characteristic into [`None`]
+fn optional<T>(result: PolarResult<T>) -> PolarResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::CharacteristicNotFound) => Ok(None),
+        Err(why) => Err(why),
+    }
+}
+
+/// Private helper to give up on `future` with [`Error::Timeout`] if it takes longer than `timeout`
+async fn within<T>(
+    timeout: Duration,
+    future: impl Future<Output = PolarResult<T>>,
+) -> PolarResult<T> {
+    time::timeout(timeout, future)
+        .await
+        .map_err(|_| Error::Timeout)?
+}
+
+/// Private helper to check a read answers within `timeout`, whatever the value is
+async fn alive(
+    timeout: Duration,
+    read: impl Future<Output = PolarResult<Vec<u8>>>,
+) -> PolarResult<()> {
+    within(timeout, read).await?;
+
+    Ok(())
+}
+
+/// Private helper to decode a string characteristic, which devices can pad with NULs
+fn string_from(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_matches(char::from(0))
+        .to_string()
+}
+
+/// Private helper to classify the value of the model number characteristic
+fn model_from(data: &[u8]) -> PolarModel {
+    PolarModel::from(string_from(data).as_str())
+}
+
+/// Private helper to check a name fits in the Device Name characteristic
+fn device_name_bytes(name: &str) -> PolarResult<&[u8]> {
+    // The longest name the Device Name characteristic can hold
+    if name.len() > 248 {
+        return Err(Error::InvalidLength);
+    }
+
+    Ok(name.as_bytes())
+}
+
+/// Private helper to get the connection change an adapter event is about, if any
+fn connection_change(event: CentralEvent) -> Option<(PeripheralId, ConnectionState)> {
+    match event {
+        CentralEvent::DeviceConnected(id) => Some((id, ConnectionState::Connected)),
+        CentralEvent::DeviceDisconnected(id) => Some((id, ConnectionState::Disconnected)),
+        _ => None,
+    }
+}
+
+/// Private helper to keep the connection changes of the device with `id`
+fn connection_states<I: PartialEq>(
+    changes: impl Stream<Item = (I, ConnectionState)>,
+    id: I,
+) -> impl Stream<Item = ConnectionState> {
+    changes
+        .filter_map(move |(peripheral, state)| future::ready((peripheral == id).then_some(state)))
+}
+
+/// Private helper to build a [`DeviceStatus`] from one read of the device properties
+fn status_from(
+    connected: bool,
+    properties: Option<PeripheralProperties>,
+    address: BDAddr,
+    active_measurements: Vec<H10MeasurementType>,
+) -> DeviceStatus {
+    let (rssi, local_name) = match properties {
+        Some(properties) => (properties.rssi, properties.local_name),
+        None => (None, None),
+    };
+
+    DeviceStatus {
+        connected,
+        rssi,
+        local_name,
+        address: address.to_string(),
+        active_measurements,
+    }
+}
+
+/// Private helper to check an ACC range in G
+fn valid_range(range: u8) -> bool {
+    range == 2 || range == 4 || range == 8
+}
+
+/// Private helper to check an ACC sample rate in hz
+fn valid_sample_rate(rate: u8) -> bool {
+    rate == 25 || rate == 50 || rate == 100 || rate == 200
+}
+
+/// Private helper to find characteristics from a [`Uuid`]
+async fn find_characteristic(device: &Peripheral, uuid: Uuid) -> PolarResult<Characteristic> {
+    characteristic_in(&device.characteristics(), uuid)
+}
+
+/// Private helper to find the characteristic with `uuid` among a device's characteristics
+fn characteristic_in(
+    characteristics: &BTreeSet<Characteristic>,
+    uuid: Uuid,
+) -> PolarResult<Characteristic> {
+    characteristics
         .iter()
         .find(|c| c.uuid == uuid)
         .ok_or(Error::CharacteristicNotFound)
         .cloned()
 }
 
+/// Private helper to check whether a stream's characteristic is among a device's characteristics
+fn has_stream_in(characteristics: &BTreeSet<Characteristic>, stream: NotifyStream) -> bool {
+    characteristic_in(characteristics, stream.into()).is_ok()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -826,6 +3091,474 @@ mod test {
         };
     }
 
+    // Characteristics a device could have, one for each notify uuid
+    fn notify_characteristics<const N: usize>(uuids: [NotifyUuid; N]) -> BTreeSet<Characteristic> {
+        uuids
+            .into_iter()
+            .map(|uuid| Characteristic {
+                uuid: uuid.into(),
+                service_uuid: Uuid::nil(),
+                properties: btleplug::api::CharPropFlags::NOTIFY,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PolarSensor>();
+    }
+
+    #[test]
+    fn reconfigure_acc() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+
+        assert!(matches!(
+            aw!(polar.reconfigure_acc(3, 50)),
+            Err(Error::InvalidData)
+        ));
+        assert!(matches!(
+            aw!(polar.reconfigure_acc(4, 60)),
+            Err(Error::InvalidData)
+        ));
+        assert!(aw!(polar.reconfigure_acc(4, 50)).is_err());
+        assert_eq!(polar.range.load(Ordering::Relaxed), 8);
+        assert_eq!(polar.sample_rate.load(Ordering::Relaxed), 200);
+    }
+
+    #[test]
+    fn first_heart_rate() {
+        let notifications = vec![
+            ValueNotification {
+                uuid: NotifyUuid::BatteryLevel.into(),
+                value: vec![87],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![0],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![0, 61],
+            },
+        ];
+        let heart_rate = aw!(wait_heart_rate(
+            futures::stream::iter(notifications),
+            Duration::from_secs(1)
+        ))
+        .unwrap();
+        assert_eq!(*heart_rate.bpm(), 61);
+
+        let pending = futures::stream::pending::<ValueNotification>();
+        assert!(matches!(
+            aw!(wait_heart_rate(pending, Duration::from_millis(10))),
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn in_charger_needs_pmd() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert!(matches!(
+            aw!(polar.is_in_charger()),
+            Err(Error::NoControlPoint)
+        ));
+    }
+
+    #[test]
+    fn shared_manager() {
+        let manager = aw!(Manager::new()).unwrap();
+
+        assert!(PolarSensor::new_with_manager("dummy ID".to_string(), manager.clone()).is_ok());
+        assert!(matches!(
+            PolarSensor::new_with_manager("short".to_string(), manager),
+            Err(Error::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn status_snapshot() {
+        let address = BDAddr::from([0xa0, 0x9e, 0x1a, 0x8c, 0x4c, 0xad]);
+        let properties = PeripheralProperties {
+            rssi: Some(-62),
+            local_name: Some("Polar H10 8C4CAD2D".to_string()),
+            ..Default::default()
+        };
+
+        let status = status_from(
+            true,
+            Some(properties),
+            address,
+            vec![H10MeasurementType::Ecg],
+        );
+        assert_eq!(
+            status,
+            DeviceStatus {
+                connected: true,
+                rssi: Some(-62),
+                local_name: Some("Polar H10 8C4CAD2D".to_string()),
+                address: "A0:9E:1A:8C:4C:AD".to_string(),
+                active_measurements: vec![H10MeasurementType::Ecg],
+            }
+        );
+
+        // Without properties only the connection and measurements are known
+        let status = status_from(false, None, address, vec![]);
+        assert_eq!(status.rssi, None);
+        assert_eq!(status.local_name, None);
+        assert!(!status.connected);
+    }
+
+    #[test]
+    fn missing_characteristics() {
+        assert_eq!(optional(Ok(5)).unwrap(), Some(5));
+        assert_eq!(
+            optional::<u8>(Err(Error::CharacteristicNotFound)).unwrap(),
+            None
+        );
+        assert!(matches!(
+            optional::<u8>(Err(Error::NoDevice)),
+            Err(Error::NoDevice)
+        ));
+
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert!(matches!(aw!(polar.device_info()), Err(Error::NoDevice)));
+    }
+
+    #[test]
+    fn powered_off_errors() {
+        assert!(powered_off(&btleplug::Error::Other(
+            "org.bluez.Error.NotReady: Resource Not Ready".into()
+        )));
+        assert!(powered_off(&btleplug::Error::Other(
+            "Adapter is powered off".into()
+        )));
+        assert!(!powered_off(&btleplug::Error::DeviceNotFound));
+
+        let error = scan_error(btleplug::Error::Other("org.bluez.Error.NotReady".into()));
+        assert!(matches!(error, Error::AdapterOff));
+        assert_eq!(
+            error.to_string(),
+            "Arctic Error: Bluetooth adapter is powered off"
+        );
+        assert!(matches!(
+            scan_error(btleplug::Error::DeviceNotFound),
+            Error::BleError(_)
+        ));
+    }
+
+    #[test]
+    fn control_point_refs() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(NotifyStream::MeasurementCP);
+
+        // Already subscribed, so neither needs the device
+        aw!(polar.acquire_control_point()).unwrap();
+        aw!(polar.acquire_control_point()).unwrap();
+        assert_eq!(aw!(polar.control_point_refs.lock()).count, 2);
+        assert!(!aw!(polar.control_point_refs.lock()).owned);
+
+        aw!(polar.release_control_point()).unwrap();
+        aw!(polar.release_control_point()).unwrap();
+        assert_eq!(aw!(polar.control_point_refs.lock()).count, 0);
+        assert!(polar.is_subscribed(NotifyStream::MeasurementCP));
+    }
+
+    #[test]
+    fn stream_capabilities() {
+        // A heart rate strap without the battery service
+        let characteristics = notify_characteristics([
+            NotifyUuid::HeartMeasurement,
+            NotifyUuid::MeasurementCP,
+            NotifyUuid::MeasurementData,
+        ]);
+
+        assert!(has_stream_in(&characteristics, NotifyStream::HeartRate));
+        assert!(has_stream_in(&characteristics, NotifyStream::MeasurementCP));
+        assert!(has_stream_in(
+            &characteristics,
+            NotifyStream::MeasurementData
+        ));
+        assert!(!has_stream_in(&characteristics, NotifyStream::Battery));
+        assert!(!has_stream_in(&BTreeSet::new(), NotifyStream::HeartRate));
+    }
+
+    #[test]
+    fn rssi_timeout() {
+        // A property read that never returns gives up instead of hanging
+        let hung = future::pending::<PolarResult<Option<i16>>>();
+        assert!(matches!(
+            aw!(within(Duration::from_millis(10), hung)),
+            Err(Error::Timeout)
+        ));
+
+        let read = future::ready(Ok(Some(-70)));
+        assert_eq!(
+            aw!(within(Duration::from_secs(5), read)).unwrap(),
+            Some(-70)
+        );
+        let failed = future::ready(Err::<Option<i16>, _>(Error::NotConnected));
+        assert!(matches!(
+            aw!(within(Duration::from_secs(5), failed)),
+            Err(Error::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn connection_priority_needs_device() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+
+        assert!(matches!(
+            aw!(polar.request_connection_priority(ConnectionPriority::High)),
+            Err(Error::NoDevice)
+        ));
+    }
+
+    #[test]
+    fn connection_events_for_device() {
+        use ConnectionState::{Connected, Disconnected};
+
+        // Changes from every adapter, only some of them for this device
+        let changes = futures::stream::iter([
+            (1, Connected),
+            (2, Connected),
+            (1, Disconnected),
+            (2, Disconnected),
+            (1, Connected),
+        ]);
+
+        let states: Vec<_> = aw!(connection_states(changes, 1).collect());
+        assert_eq!(states, vec![Connected, Disconnected, Connected]);
+    }
+
+    #[test]
+    fn multi_frame_response() {
+        let cp = |value: Vec<u8>| ValueNotification {
+            uuid: NotifyUuid::MeasurementCP.into(),
+            value,
+        };
+        let notifications = vec![
+            cp(vec![0xf0, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0x32, 0x00]),
+            ValueNotification {
+                uuid: NotifyUuid::BatteryLevel.into(),
+                value: vec![80],
+            },
+            cp(vec![0xf0, 0x01, 0x02, 0x00, 0x00, 0x01, 0x01, 0x10, 0x00]),
+            cp(vec![0xf0, 0x02, 0x02, 0x00, 0x00]),
+        ];
+
+        let response = aw!(collect_response(futures::stream::iter(notifications))).unwrap();
+        assert!(!response.has_more_frames());
+        assert_eq!(
+            *response.parameters(),
+            vec![0x00, 0x01, 0x32, 0x00, 0x01, 0x01, 0x10, 0x00]
+        );
+        let settings = StreamSettings::new(&response).unwrap();
+        assert_eq!(settings.resolution(), 16);
+        assert_eq!(settings.supported_sample_rates(), vec![50]);
+
+        // The stream ending before the last frame is an error
+        let truncated = vec![cp(vec![
+            0xf0, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0x32, 0x00,
+        ])];
+        assert!(matches!(
+            aw!(collect_response(futures::stream::iter(truncated))),
+            Err(Error::NoDevice)
+        ));
+    }
+
+    #[test]
+    fn rediscover_forgets_features() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        *polar.features.lock().unwrap() = Some(SupportedFeatures::new(0b00000101));
+        polar
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(NotifyStream::HeartRate);
+
+        polar.forget_services();
+        assert_eq!(*polar.features.lock().unwrap(), None);
+        assert!(polar.is_subscribed(NotifyStream::HeartRate));
+    }
+
+    #[test]
+    fn reconnect_retries() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.scan_duration(Duration::from_micros(10));
+
+        // Succeeds on the third attempt
+        let attempts = AtomicUsize::new(0);
+        let connect = || async {
+            match attempts.fetch_add(1, Ordering::Relaxed) {
+                0 | 1 => Err(Error::NoDevice),
+                _ => Ok(()),
+            }
+        };
+        assert!(aw!(polar.retry_connect(&Handler, connect)).unwrap());
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+
+        // Gives up with the last error instead of retrying forever
+        let attempts = AtomicUsize::new(0);
+        let connect = || async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(Error::Timeout)
+        };
+        assert!(matches!(
+            aw!(polar.retry_connect(&Handler, connect)),
+            Err(Error::Timeout)
+        ));
+        assert_eq!(attempts.load(Ordering::Relaxed), RECONNECT_ATTEMPTS);
+
+        // Stops without trying when the handler asks to
+        let attempts = AtomicUsize::new(0);
+        let connect = || async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        };
+        assert!(!aw!(polar.retry_connect(&Stopped, connect)).unwrap());
+        assert_eq!(attempts.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn cancelled_event_loop() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler(Handler);
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+
+        // Without cancelling, dispatching a stream that never ends would never return
+        aw!(async {
+            tokio::spawn(async move {
+                time::sleep(Duration::from_millis(20)).await;
+                cancel.cancel();
+            });
+
+            let events = polar.dispatch(&Handler, futures::stream::pending());
+            time::timeout(
+                Duration::from_secs(5),
+                polar.run_until(events, token.cancelled()),
+            )
+            .await
+        })
+        .expect("event loop wasn't cancelled")
+        .unwrap();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn model_number() {
+        assert_eq!(model_from(b"H10\0"), PolarModel::H10);
+        assert_eq!(model_from(b"OH1\0\0\0"), PolarModel::OH1);
+        assert_eq!(model_from(b"Polar Sense"), PolarModel::VeritySense);
+        assert_eq!(model_from(b"H7\0"), PolarModel::Unknown("H7".to_string()));
+        assert_eq!(model_from(b""), PolarModel::Unknown(String::new()));
+    }
+
+    #[test]
+    fn device_names() {
+        assert_eq!(string_from(b"Polar H10 Strap 2\0\0"), "Polar H10 Strap 2");
+        assert_eq!(string_from(b""), "");
+
+        assert_eq!(
+            device_name_bytes("Polar H10 Strap 2").unwrap(),
+            b"Polar H10 Strap 2"
+        );
+        assert_eq!(device_name_bytes(&"a".repeat(248)).unwrap().len(), 248);
+        assert!(matches!(
+            device_name_bytes(&"a".repeat(249)),
+            Err(Error::InvalidLength)
+        ));
+        // The limit is in bytes, not characters
+        assert!(device_name_bytes(&"é".repeat(124)).is_ok());
+        assert!(matches!(
+            device_name_bytes(&"é".repeat(125)),
+            Err(Error::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn subscribe_before_connect() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+
+        let why = aw!(polar.subscribe(NotifyStream::HeartRate)).unwrap_err();
+        assert!(matches!(why, Error::NotConnected));
+        assert!(why.to_string().contains("connect"));
+        assert!(matches!(
+            aw!(polar.unsubscribe(NotifyStream::HeartRate)),
+            Err(Error::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn reset_clears_state() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar
+            .active_measurements
+            .lock()
+            .unwrap()
+            .insert(H10MeasurementType::Ecg, None);
+        polar
+            .last_time_stamps
+            .lock()
+            .unwrap()
+            .insert(H10MeasurementType::Ecg, 1_000);
+        polar
+            .subscriptions
+            .lock()
+            .unwrap()
+            .extend([NotifyStream::HeartRate, NotifyStream::MeasurementData]);
+
+        // Nothing can be stopped on a lost connection, but everything is still forgotten
+        assert!(aw!(polar.stop_everything()).is_err());
+        assert!(polar.active_measurements().is_empty());
+        assert!(polar.last_time_stamps.lock().unwrap().is_empty());
+        assert!(!polar.is_subscribed(NotifyStream::HeartRate));
+        assert!(!polar.is_subscribed(NotifyStream::MeasurementData));
+
+        // With nothing left to undo there's nothing to fail
+        aw!(polar.stop_everything()).unwrap();
+    }
+
+    #[test]
+    fn battery_status_needs_device() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+
+        assert!(matches!(aw!(polar.battery_status()), Err(Error::NoDevice)));
+    }
+
+    #[test]
+    fn ping_liveness() {
+        let timeout = Duration::from_millis(10);
+
+        // Any answer means the link is alive, even one that isn't a valid battery level
+        assert!(aw!(alive(timeout, future::ready(Ok(vec![80])))).is_ok());
+        assert!(aw!(alive(timeout, future::ready(Ok(vec![])))).is_ok());
+
+        // A dead link never answers
+        assert!(matches!(
+            aw!(alive(timeout, future::pending())),
+            Err(Error::Timeout)
+        ));
+        assert!(matches!(
+            aw!(alive(timeout, future::ready(Err(Error::NotConnected)))),
+            Err(Error::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn samples_per_packet() {
+        assert_eq!(H10MeasurementType::Ecg.expected_samples_per_packet(14), 73);
+        assert_eq!(H10MeasurementType::Acc.expected_samples_per_packet(16), 36);
+        assert_eq!(H10MeasurementType::Acc.expected_samples_per_packet(8), 73);
+        assert_eq!(H10MeasurementType::Acc.expected_samples_per_packet(24), 24);
+    }
+
     #[test]
     fn type_push() {
         let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
@@ -868,4 +3601,627 @@ mod test {
         polar.data_type_pop(H10MeasurementType::Ecg);
         assert_eq!(polar.data_type, None);
     }
+
+    struct Handler;
+
+    impl EventHandler for Handler {}
+
+    struct Stopped;
+
+    #[async_trait]
+    impl EventHandler for Stopped {
+        async fn should_continue(&self) -> bool {
+            false
+        }
+    }
+
+    struct Recorder {
+        battery: Mutex<Vec<u8>>,
+        bpm: Mutex<Vec<u8>>,
+        bad: Mutex<Vec<Vec<u8>>>,
+        quality: Mutex<Vec<SignalQuality>>,
+    }
+
+    #[async_trait]
+    impl EventHandler for Recorder {
+        async fn battery_update(&self, battery_level: u8) {
+            self.battery.lock().unwrap().push(battery_level);
+        }
+
+        async fn heart_rate_update(&self, _ctx: &PolarSensor, heartrate: HeartRate) {
+            self.bpm.lock().unwrap().push(*heartrate.bpm());
+        }
+
+        async fn on_parse_error(&self, _ctx: &PolarSensor, _uuid: Uuid, data: &[u8]) {
+            self.bad.lock().unwrap().push(data.to_vec());
+        }
+
+        async fn signal_quality(&self, _ctx: &PolarSensor, quality: SignalQuality) {
+            self.quality.lock().unwrap().push(quality);
+        }
+    }
+
+    struct BatteryRecorder(Mutex<Vec<Battery>>);
+
+    #[async_trait]
+    impl EventHandler for BatteryRecorder {
+        async fn battery_status_update(&self, _ctx: &PolarSensor, battery: Battery) {
+            self.0.lock().unwrap().push(battery);
+        }
+    }
+
+    struct Spo2Recorder(Mutex<Vec<Spo2>>);
+
+    #[async_trait]
+    impl EventHandler for Spo2Recorder {
+        async fn spo2_update(&self, _ctx: &PolarSensor, spo2: Spo2) {
+            self.0.lock().unwrap().push(spo2);
+        }
+    }
+
+    #[test]
+    fn spo2_update() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let recorder = Arc::new(Spo2Recorder(Mutex::new(vec![])));
+        polar.event_handler = Some(recorder.clone());
+
+        let notifications = vec![
+            ValueNotification {
+                uuid: NotifyUuid::Spo2.into(),
+                value: vec![0x00, 0x62, 0x00, 0x48, 0x00],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::Spo2.into(),
+                value: vec![0x00, 0x62],
+            },
+        ];
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        let updates = recorder.0.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].spo2(), Some(98.0));
+        assert_eq!(polar.parse_error_count(), 1);
+        assert!(matches!(
+            aw!(polar.subscribe_if_available(NotifyStream::Spo2)),
+            Err(Error::NoDevice)
+        ));
+    }
+
+    #[test]
+    fn spo2_not_available() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        // Heart rate straps only have the heart rate and PMD streams
+        let characteristics =
+            notify_characteristics([NotifyUuid::HeartMeasurement, NotifyUuid::MeasurementData]);
+
+        assert!(!aw!(polar.subscribe_if_in(&characteristics, NotifyStream::Spo2)).unwrap());
+        // A stream the device has is subscribed to, which needs a connection
+        assert!(matches!(
+            aw!(polar.subscribe_if_in(&characteristics, NotifyStream::HeartRate)),
+            Err(Error::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn battery_status_update() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let recorder = Arc::new(BatteryRecorder(Mutex::new(vec![])));
+        polar.event_handler = Some(recorder.clone());
+
+        let notification = || ValueNotification {
+            uuid: NotifyUuid::BatteryLevel.into(),
+            value: vec![64],
+        };
+        aw!(polar.event_loop_with(futures::stream::iter([notification()]))).unwrap();
+
+        // The charging state read when connecting is passed on
+        *polar.charging.lock().unwrap() = Some(true);
+        aw!(polar.event_loop_with(futures::stream::iter([notification()]))).unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                Battery {
+                    level: 64,
+                    charging: None
+                },
+                Battery {
+                    level: 64,
+                    charging: Some(true)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn event_loop_with_stream() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let recorder = Arc::new(Recorder {
+            battery: Mutex::new(vec![]),
+            bpm: Mutex::new(vec![]),
+            bad: Mutex::new(vec![]),
+            quality: Mutex::new(vec![]),
+        });
+        polar.event_handler = Some(recorder.clone());
+        polar.signal_quality_estimator(SignalQualityEstimator::new(2, 2, 0, 1000));
+
+        let notifications = vec![
+            ValueNotification {
+                uuid: NotifyUuid::BatteryLevel.into(),
+                value: vec![87],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![0, 61],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![16, 62, 55, 4],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::MeasurementData.into(),
+                value: vec![0x07; 12],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::MeasurementData.into(),
+                value: vec![
+                    0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff,
+                    0xff, 0xff, 0xff,
+                ],
+            },
+        ];
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        assert_eq!(*recorder.battery.lock().unwrap(), vec![87]);
+        assert_eq!(*recorder.bpm.lock().unwrap(), vec![61, 62]);
+        assert!(polar.rr_supported());
+        assert_eq!(
+            *recorder.quality.lock().unwrap(),
+            vec![SignalQuality {
+                flat: true,
+                saturated: false
+            }]
+        );
+        assert_eq!(*recorder.bad.lock().unwrap(), vec![vec![0x07; 12]]);
+        assert_eq!(polar.parse_error_count(), 1);
+    }
+
+    #[test]
+    fn record_session() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let session = aw!(polar.record_session(Duration::from_millis(10))).unwrap();
+        assert!(session.is_empty());
+
+        let notifications = vec![
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![0, 61],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::MeasurementData.into(),
+                value: vec![
+                    0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff,
+                ],
+            },
+        ];
+        let recorder = SessionRecorder::new();
+        aw!(polar.dispatch(&recorder, futures::stream::iter(notifications))).unwrap();
+
+        let session = recorder.finish();
+        assert_eq!(session.heart_rate().len(), 1);
+        assert_eq!(*session.heart_rate()[0].1.bpm(), 61);
+        assert_eq!(session.measurements().len(), 1);
+        assert_eq!(
+            session.measurements()[0].1.data_type(),
+            &H10MeasurementType::Ecg
+        );
+    }
+
+    #[test]
+    fn bad_notifications_skipped() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let recorder = Arc::new(Recorder {
+            battery: Mutex::new(vec![]),
+            bpm: Mutex::new(vec![]),
+            bad: Mutex::new(vec![]),
+            quality: Mutex::new(vec![]),
+        });
+        polar.event_handler = Some(recorder.clone());
+
+        let notifications = vec![
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![0],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::BatteryLevel.into(),
+                value: vec![],
+            },
+            ValueNotification {
+                uuid: NotifyUuid::HeartMeasurement.into(),
+                value: vec![0, 61],
+            },
+        ];
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        assert_eq!(*recorder.bpm.lock().unwrap(), vec![61]);
+        assert_eq!(*recorder.bad.lock().unwrap(), vec![vec![0], vec![]]);
+        assert_eq!(polar.parse_error_count(), 2);
+    }
+
+    #[test]
+    fn replace_event_handler() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+
+        assert!(polar.event_handler(Handler).is_none());
+        assert!(polar.event_handler(Handler).is_some());
+        assert!(polar.clear_event_handler().is_some());
+        assert!(polar.clear_event_handler().is_none());
+        assert!(polar.event_handler.is_none());
+    }
+
+    #[test]
+    fn measurement_control() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler(Handler);
+        let control = polar.measurement_control();
+
+        assert!(matches!(
+            aw!(control.stop(H10MeasurementType::Acc)),
+            Err(Error::LoopNotRunning)
+        ));
+
+        let (tx, rx) = futures::channel::mpsc::unbounded::<ValueNotification>();
+        let (result, response) = aw!(async {
+            tokio::join!(polar.event_loop_with(rx), async {
+                let response = loop {
+                    match control.start(H10MeasurementType::Acc).await {
+                        Err(Error::LoopNotRunning) => tokio::task::yield_now().await,
+                        response => break response,
+                    }
+                };
+                drop(tx);
+                response
+            })
+        });
+
+        // The request reached the loop, which has no device to send it to
+        result.unwrap();
+        assert!(matches!(response, Err(Error::NoDevice)));
+        assert!(matches!(
+            aw!(control.start(H10MeasurementType::Acc)),
+            Err(Error::LoopNotRunning)
+        ));
+    }
+
+    #[test]
+    fn acc_calibration_applied() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler(Handler);
+        let mut rx = polar.measurement_channel(1);
+        polar.acc_calibration(AccCalibration {
+            x_off: -20,
+            y_off: 0,
+            z_off: 1000,
+            scale: 0.5,
+        });
+
+        let notifications = vec![ValueNotification {
+            uuid: NotifyUuid::MeasurementData.into(),
+            value: vec![
+                0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x01, 0x0a, 0x00, 0x14, 0x00,
+                0xe8, 0x03,
+            ],
+        }];
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        let read = rx.try_recv().unwrap();
+        assert_eq!(
+            read.into_acc(),
+            Some(vec![Acc::from_xyz(Axes3 { x: 15, y: 10, z: 0 })])
+        );
+    }
+
+    #[test]
+    fn out_of_order_packets() {
+        struct OrderRecorder(Mutex<Vec<(u64, u64)>>);
+
+        #[async_trait]
+        impl EventHandler for OrderRecorder {
+            async fn on_out_of_order(&self, _ctx: &PolarSensor, newest: u64, data: &PmdRead) {
+                self.0.lock().unwrap().push((newest, data.time_stamp()));
+            }
+        }
+
+        let ecg = |time_stamp: u64| {
+            let mut value = vec![0x00];
+            value.extend(time_stamp.to_le_bytes());
+            value.extend([0x00, 0x01, 0x00, 0x00]);
+            ValueNotification {
+                uuid: NotifyUuid::MeasurementData.into(),
+                value,
+            }
+        };
+        let notifications = || vec![ecg(100), ecg(100), ecg(50), ecg(200)];
+
+        let recorder = Arc::new(OrderRecorder(Mutex::new(vec![])));
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler = Some(recorder.clone());
+        let mut rx = polar.measurement_channel(8);
+
+        aw!(polar.event_loop_with(futures::stream::iter(notifications()))).unwrap();
+        assert_eq!(polar.out_of_order_count(), 2);
+        assert_eq!(*recorder.0.lock().unwrap(), vec![(100, 100), (100, 50)]);
+        assert_eq!((0..4).filter(|_| rx.try_recv().is_ok()).count(), 4);
+
+        // The newest timestamp is kept, so the same packets again are all out of order
+        polar.skip_out_of_order(true);
+        aw!(polar.event_loop_with(futures::stream::iter(notifications()))).unwrap();
+        assert_eq!(polar.out_of_order_count(), 6);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn decimation() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler(Handler);
+        let mut rx = polar.measurement_channel(4);
+        polar.sample_history(8);
+        polar.set_decimation(H10MeasurementType::Ecg, 4);
+
+        let packet = vec![
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0x01, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x03, 0x00, 0x00,
+        ];
+        let notifications = (0..2).map(|_| ValueNotification {
+            uuid: NotifyUuid::MeasurementData.into(),
+            value: packet.clone(),
+        });
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        // Samples 0 and 4 of the 6 received are kept
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.clone().into_ecg().unwrap(), vec![Ecg::from_val(1)]);
+        assert!(first.time_stamp() < 599618164814402794u64);
+        let second = rx.try_recv().unwrap();
+        assert_eq!(second.into_ecg().unwrap(), vec![Ecg::from_val(2)]);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(polar.recent_ecg(8).len(), 6);
+    }
+
+    #[test]
+    fn delivery_filter() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler(Handler);
+        let mut rx = polar.measurement_channel(4);
+        polar.sample_history(8);
+        polar.set_delivery_filter(&[H10MeasurementType::Ecg]);
+
+        let ecg = vec![
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0x01, 0x00, 0x00,
+        ];
+        let acc = vec![
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x01, 0x01, 0x00, 0x02, 0x00,
+            0x03, 0x00,
+        ];
+        let notifications = [acc.clone(), ecg, acc]
+            .into_iter()
+            .map(|value| ValueNotification {
+                uuid: NotifyUuid::MeasurementData.into(),
+                value,
+            });
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        let read = rx.try_recv().unwrap();
+        assert_eq!(*read.data_type(), H10MeasurementType::Ecg);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(polar.recent_acc(8).len(), 2);
+
+        polar.clear_delivery_filter();
+        assert!(polar.delivered(H10MeasurementType::Acc));
+    }
+
+    #[test]
+    fn measurement_channel_drops() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        polar.event_handler(Handler);
+        let mut rx = polar.measurement_channel(1);
+        polar.sample_history(2);
+
+        let packet = vec![
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff,
+        ];
+        let notifications = (0..3).map(|_| ValueNotification {
+            uuid: NotifyUuid::MeasurementData.into(),
+            value: packet.clone(),
+        });
+        aw!(polar.event_loop_with(futures::stream::iter(notifications))).unwrap();
+
+        assert_eq!(polar.dropped_packets(), 2);
+        assert_eq!(polar.recent_ecg(5).len(), 2);
+        assert_eq!(*polar.recent_ecg(1)[0].val(), -1);
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn batch_subscribe() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+
+        let result = aw!(polar.subscribe_all(&[NotifyStream::Battery, NotifyStream::HeartRate]));
+        assert!(!result.all_ok());
+        assert!(result.succeeded().is_empty());
+        assert_eq!(result.failed().len(), 2);
+        assert!(matches!(result.failed()[0].1, Error::NotConnected));
+        assert_eq!(result.results()[1].0, NotifyStream::HeartRate);
+
+        let result = BatchResult {
+            results: vec![
+                (NotifyStream::Battery, Ok(())),
+                (NotifyStream::MeasurementData, Err(Error::NoControlPoint)),
+            ],
+        };
+        assert_eq!(result.succeeded(), vec![NotifyStream::Battery]);
+        assert_eq!(result.failed()[0].0, NotifyStream::MeasurementData);
+    }
+
+    #[test]
+    fn adapter_name() {
+        assert!(adapter_matches("hci0 (usb:v1D6Bp0246d0537)", "hci0"));
+        assert!(adapter_matches(
+            "hci0 (usb:v1D6Bp0246d0537)",
+            "hci0 (usb:v1D6Bp0246d0537)"
+        ));
+        assert!(!adapter_matches("hci10 (usb:v1D6Bp0246d0537)", "hci1"));
+        assert!(!adapter_matches("", "hci0"));
+    }
+
+    #[test]
+    fn subscription_state() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert!(!polar.is_subscribed(NotifyStream::HeartRate));
+
+        polar
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(NotifyStream::HeartRate);
+        assert!(polar.is_subscribed(NotifyStream::HeartRate));
+        assert!(!polar.is_subscribed(NotifyStream::Battery));
+    }
+
+    #[test]
+    fn from_config() {
+        let mut config = PolarConfig::new("dummy ID".to_string());
+        config.data_types = vec![H10MeasurementType::Acc];
+        config.range = 4;
+        config.sample_rate = 50;
+        config.auto_reconnect = true;
+        config.scan_duration = Duration::from_secs(5);
+
+        let polar = aw!(PolarSensor::with_config(config.clone())).unwrap();
+        assert_eq!(polar.data_type, Some(vec![H10MeasurementType::Acc]));
+        assert_eq!(polar.range.load(Ordering::Relaxed), 4);
+        assert_eq!(polar.sample_rate.load(Ordering::Relaxed), 50);
+        assert!(polar.auto_reconnect);
+        assert_eq!(polar.scan_duration, Duration::from_secs(5));
+
+        config.range = 3;
+        assert!(matches!(
+            aw!(PolarSensor::with_config(config)),
+            Err(Error::InvalidData)
+        ));
+
+        let polar = aw!(PolarSensor::with_config(PolarConfig::new(
+            "dummy ID".to_string()
+        )))
+        .unwrap();
+        assert_eq!(polar.data_type, None);
+        assert_eq!(polar.scan_duration, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn resolution_needs_acc() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert!(matches!(aw!(polar.resolution(8)), Err(Error::NoDataType)));
+
+        polar.data_type_push(H10MeasurementType::Ecg);
+        assert!(matches!(aw!(polar.resolution(8)), Err(Error::WrongType)));
+
+        polar.data_type_push(H10MeasurementType::Acc);
+        assert!(matches!(aw!(polar.resolution(8)), Err(Error::NoDevice)));
+        assert_eq!(polar.resolution, 16);
+    }
+
+    #[test]
+    fn resolution_from_settings() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        // ACC settings with 8 and 16 bit resolutions
+        let response = aw!(ControlResponse::new(vec![
+            0xf0, 0x01, 0x02, 0x00, 0x00, 0x00, 0x01, 0x32, 0x00, 0x01, 0x02, 0x10, 0x00, 0x08,
+            0x00
+        ]))
+        .unwrap();
+        let settings = StreamSettings::new(&response).unwrap();
+
+        polar.resolution_from(&settings, 8).unwrap();
+        assert_eq!(polar.resolution, 8);
+        assert!(matches!(
+            polar.resolution_from(&settings, 12),
+            Err(Error::InvalidData)
+        ));
+        assert_eq!(polar.resolution, 8);
+    }
+
+    #[test]
+    fn no_pmd() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert!(!polar.has_pmd());
+    }
+
+    #[test]
+    fn available_types() {
+        assert_eq!(
+            SupportedFeatures::new(0b00000101).available_types(),
+            vec![H10MeasurementType::Ecg, H10MeasurementType::Acc]
+        );
+        assert_eq!(
+            SupportedFeatures::new(0b01101100).available_types(),
+            vec![H10MeasurementType::Acc]
+        );
+        assert!(SupportedFeatures::new(0).available_types().is_empty());
+    }
+
+    #[test]
+    fn cached_features() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert!(matches!(aw!(polar.features()), Err(Error::NoDevice)));
+
+        let features = SupportedFeatures::new(0b00000101);
+        *polar.features.lock().unwrap() = Some(features);
+        assert_eq!(aw!(polar.features()).unwrap(), features);
+        assert!(matches!(
+            aw!(polar.refresh_features()),
+            Err(Error::NoDevice)
+        ));
+    }
+
+    #[test]
+    fn write_type() {
+        let mut polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert_eq!(polar.write_type, ControlWriteType::WithResponse);
+
+        polar.control_write_type(ControlWriteType::Fallback);
+        assert_eq!(polar.write_type, ControlWriteType::Fallback);
+    }
+
+    #[test]
+    fn effective_rate() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        assert_eq!(polar.effective_sample_rate(H10MeasurementType::Acc), None);
+
+        let start = aw!(ControlResponse::new(vec![
+            0xf0, 0x02, 0x02, 0x00, 0x00, 0x00, 0x01, 0x64, 0x00
+        ]))
+        .unwrap();
+        polar.record_response(
+            &ControlPointCommand::RequestMeasurementStart,
+            H10MeasurementType::Acc,
+            &start,
+        );
+        assert_eq!(
+            polar.effective_sample_rate(H10MeasurementType::Acc),
+            Some(100)
+        );
+        assert_eq!(polar.active_measurements(), vec![H10MeasurementType::Acc]);
+
+        let stop = aw!(ControlResponse::new(vec![0xf0, 0x03, 0x02, 0x00, 0x00])).unwrap();
+        polar.record_response(
+            &ControlPointCommand::StopMeasurement,
+            H10MeasurementType::Acc,
+            &stop,
+        );
+        assert_eq!(polar.effective_sample_rate(H10MeasurementType::Acc), None);
+        assert!(polar.active_measurements().is_empty());
+    }
 }
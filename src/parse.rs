@@ -0,0 +1,201 @@
+//! # Parse
+//!
+//! Parse contains the entry points for parsing raw data received from a device. The data comes
+//! straight off the radio, so these return an error for any malformed input rather than
+//! panicking. The `fuzz` directory holds a `cargo fuzz` target that checks this, run it with
+//! `cargo +nightly fuzz run parsers`.
+//!
+//! [`dispatch_notification`] picks the parser from the notification's UUID, for apps that
+//! receive notifications themselves instead of running [`crate::PolarSensor::event_loop`].
+//!
+
+use crate::polar_uuid::NotifyUuid;
+use crate::{ControlResponse, HeartRate, ParseError, PmdRead, PolarResult, Spo2, StreamSettings};
+
+use uuid::Uuid;
+
+/// A notification parsed by [`dispatch_notification`]
+#[derive(Debug)]
+pub enum ParsedNotification {
+    /// Battery level in percent
+    Battery(u8),
+    /// Heart rate measurement
+    HeartRate(HeartRate),
+    /// PMD measurement data
+    Measurement(PmdRead),
+    /// PMD control point response
+    Control(ControlResponse),
+    /// Pulse oximeter measurement
+    Spo2(Spo2),
+    /// Notification from a characteristic this crate doesn't parse, with its UUID and value
+    Unknown(Uuid, Vec<u8>),
+}
+
+/// Parse a notification from `uuid` with the parser the event loop would use
+///
+/// ECG samples are read as 24 bit values, see [`PmdRead::with_ecg_resolution`] to parse them
+/// with another resolution.
+///
+/// # Errors
+///
+/// Returns the error of the parser for `uuid`, or [`crate::Error::Parse`] if a battery
+/// notification is empty.
+pub fn dispatch_notification(uuid: Uuid, value: &[u8]) -> PolarResult<ParsedNotification> {
+    if uuid == NotifyUuid::BatteryLevel.into() {
+        ParseError::check("battery level", value, 0, 1)?;
+        Ok(ParsedNotification::Battery(value[0]))
+    } else if uuid == NotifyUuid::HeartMeasurement.into() {
+        parse_heart_rate(value).map(ParsedNotification::HeartRate)
+    } else if uuid == NotifyUuid::MeasurementData.into() {
+        parse_pmd(value).map(ParsedNotification::Measurement)
+    } else if uuid == NotifyUuid::MeasurementCP.into() {
+        parse_control_response(value).map(ParsedNotification::Control)
+    } else if uuid == NotifyUuid::Spo2.into() {
+        parse_spo2(value).map(ParsedNotification::Spo2)
+    } else {
+        Ok(ParsedNotification::Unknown(uuid, value.to_vec()))
+    }
+}
+
+/// Parse a PMD data notification, see [`PmdRead::validate`] for the errors returned
+pub fn parse_pmd(raw: &[u8]) -> PolarResult<PmdRead> {
+    PmdRead::try_from(raw)
+}
+
+/// Parse a heart rate measurement notification
+pub fn parse_heart_rate(raw: &[u8]) -> PolarResult<HeartRate> {
+    HeartRate::try_from(raw)
+}
+
+/// Parse a pulse oximeter continuous measurement notification
+pub fn parse_spo2(raw: &[u8]) -> PolarResult<Spo2> {
+    Spo2::try_from(raw)
+}
+
+/// Parse a PMD control point response notification
+pub fn parse_control_response(raw: &[u8]) -> PolarResult<ControlResponse> {
+    ControlResponse::try_from(raw)
+}
+
+/// Parse a PMD control point response to a settings request into [`StreamSettings`]
+pub fn parse_stream_settings(raw: &[u8]) -> PolarResult<StreamSettings> {
+    StreamSettings::new(&parse_control_response(raw)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    // Run every parser over `data` and all of its prefixes
+    fn parse_all(data: &[u8]) {
+        for len in 0..=data.len() {
+            let raw = &data[..len];
+            let _ = parse_pmd(raw);
+            let _ = parse_heart_rate(raw);
+            let _ = parse_spo2(raw);
+            let _ = parse_control_response(raw);
+            let _ = parse_stream_settings(raw);
+        }
+    }
+
+    #[test]
+    fn truncated_input() {
+        // Valid ACC data, heart rate and settings responses cut short at every length
+        parse_all(&[
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x01, 0x45, 0xff, 0xe4, 0xff,
+            0xb5, 0x03,
+        ]);
+        parse_all(&[16, 60, 55, 4, 7, 3]);
+        parse_all(&[
+            0xf0, 0x01, 0x02, 0x00, 0x00, 0x00, 0x04, 0x19, 0x00, 0x32, 0x00, 0x64, 0x00, 0xC8,
+            0x00, 0x01, 0x01, 0x10, 0x00, 0x02, 0x03, 0x02, 0x00, 0x04, 0x00, 0x08, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn arbitrary_input() {
+        // Simple xorshift so the inputs are the same on every run
+        let mut state = 0x2545_f491_u32;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        for _ in 0..2000 {
+            let len = (next() % 64) as usize;
+            let mut data: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            // Steer some inputs past the first checks of each parser
+            match data.first_mut() {
+                Some(first) if len.is_multiple_of(3) => *first = 0xf0,
+                Some(first) if len % 3 == 1 => *first &= 0x02,
+                _ => {}
+            }
+            if len > 2 && len.is_multiple_of(2) {
+                data[1] = 0x01;
+                data[2] &= 0x02;
+            }
+
+            parse_all(&data);
+        }
+    }
+
+    #[test]
+    fn dispatch() {
+        let parse = |uuid: NotifyUuid, value: &[u8]| dispatch_notification(uuid.into(), value);
+
+        assert!(matches!(
+            parse(NotifyUuid::BatteryLevel, &[87]),
+            Ok(ParsedNotification::Battery(87))
+        ));
+        assert!(matches!(
+            parse(NotifyUuid::BatteryLevel, &[]),
+            Err(Error::Parse(_))
+        ));
+        match parse(NotifyUuid::HeartMeasurement, &[0, 61]) {
+            Ok(ParsedNotification::HeartRate(hr)) => assert_eq!(*hr.bpm(), 61),
+            other => panic!("Expected heart rate, got {:?}", other),
+        }
+        assert!(matches!(
+            parse(
+                NotifyUuid::MeasurementData,
+                &[0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff]
+            ),
+            Ok(ParsedNotification::Measurement(_))
+        ));
+        assert!(matches!(
+            parse(NotifyUuid::MeasurementCP, &[0xf0, 0x02, 0x02, 0x00]),
+            Ok(ParsedNotification::Control(_))
+        ));
+        assert!(matches!(
+            parse(NotifyUuid::Spo2, &[0x00, 0x62, 0x00, 0x48, 0x00]),
+            Ok(ParsedNotification::Spo2(_))
+        ));
+        assert!(parse(NotifyUuid::MeasurementData, &[0x00]).is_err());
+
+        let other = Uuid::from_u128(0x00002a00_0000_1000_8000_00805f9b34fb);
+        match dispatch_notification(other, &[1, 2]) {
+            Ok(ParsedNotification::Unknown(uuid, value)) => {
+                assert_eq!(uuid, other);
+                assert_eq!(value, vec![1, 2]);
+            }
+            other => panic!("Expected unknown notification, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_settings() {
+        assert!(parse_stream_settings(&[0xf0, 0x01, 0x00, 0x00, 0x00]).is_err());
+        assert!(parse_stream_settings(&[0xf0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x82]).is_err());
+
+        // A setting with no values is skipped
+        let settings = parse_stream_settings(&[
+            0xf0, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x01, 0x0e, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(settings.resolution(), 14);
+        assert_eq!(*settings.range(), None);
+    }
+}
@@ -7,9 +7,14 @@ use uuid::Uuid;
 
 /// Battery notify stream
 const BATTERY_LEVEL_UUID: Uuid = Uuid::from_u128(0x00002a19_0000_1000_8000_00805f9b34fb);
+/// Pulse oximeter continuous measurement notify stream
+const PLX_CONTINUOUS_MEASUREMENT_UUID: Uuid =
+    Uuid::from_u128(0x00002a5f_0000_1000_8000_00805f9b34fb);
+const BATTERY_POWER_STATE_UUID: Uuid = Uuid::from_u128(0x00002a1a_0000_1000_8000_00805f9b34fb);
 /// Heart rate notify stream
 const HEART_RATE_SERVICE_UUID: Uuid = Uuid::from_u128(0x00002a37_0000_1000_8000_00805f9b34fb);
 const BODY_LOCATION_UUID: Uuid = Uuid::from_u128(0x00002a38_0000_1000_8000_00805f9b34fb);
+const HEART_RATE_CONTROL_POINT_UUID: Uuid = Uuid::from_u128(0x00002a39_0000_1000_8000_00805f9b34fb);
 
 const PMD_CP_UUID: Uuid = Uuid::from_u128(0xfb005c81_02e7_f387_1cad_8acd2d8df0c8);
 const PMD_DATA_UUID: Uuid = Uuid::from_u128(0xfb005c82_02e7_f387_1cad_8acd2d8df0c8);
@@ -21,6 +26,8 @@ const FIRMWARE_REVISION_STRING_UUID: Uuid = Uuid::from_u128(0x00002a26_0000_1000
 const SOFTWARE_REVISION_STRING_UUID: Uuid = Uuid::from_u128(0x00002a28_0000_1000_8000_00805f9b34fb);
 const SERIAL_NUMBER_STRING_UUID: Uuid = Uuid::from_u128(0x00002a25_0000_1000_8000_00805f9b34fb);
 const SYSTEM_ID_UUID: Uuid = Uuid::from_u128(0x00002a23_0000_1000_8000_00805f9b34fb);
+const DEVICE_NAME_UUID: Uuid = Uuid::from_u128(0x00002a00_0000_1000_8000_00805f9b34fb);
+const CURRENT_TIME_UUID: Uuid = Uuid::from_u128(0x00002a2b_0000_1000_8000_00805f9b34fb);
 
 /// Which UUID to send BLE messages to
 pub enum NotifyUuid {
@@ -28,6 +35,7 @@ pub enum NotifyUuid {
     HeartMeasurement,
     MeasurementCP,
     MeasurementData,
+    Spo2,
 }
 
 impl From<NotifyStream> for NotifyUuid {
@@ -37,6 +45,7 @@ impl From<NotifyStream> for NotifyUuid {
             NotifyStream::HeartRate => NotifyUuid::HeartMeasurement,
             NotifyStream::MeasurementData => NotifyUuid::MeasurementData,
             NotifyStream::MeasurementCP => NotifyUuid::MeasurementCP,
+            NotifyStream::Spo2 => NotifyUuid::Spo2,
         }
     }
 }
@@ -48,11 +57,13 @@ impl From<NotifyUuid> for Uuid {
             NotifyUuid::HeartMeasurement => HEART_RATE_SERVICE_UUID,
             NotifyUuid::MeasurementCP => PMD_CP_UUID,
             NotifyUuid::MeasurementData => PMD_DATA_UUID,
+            NotifyUuid::Spo2 => PLX_CONTINUOUS_MEASUREMENT_UUID,
         }
     }
 }
 
 pub enum StringUuid {
+    BatteryPowerState,
     BodyLocation,
     ModelNumber,
     ManufacturerName,
@@ -61,11 +72,15 @@ pub enum StringUuid {
     SoftwareRevision,
     SerialNumber,
     SystemId,
+    DeviceName,
+    CurrentTime,
+    HeartRateControlPoint,
 }
 
 impl From<StringUuid> for Uuid {
     fn from(item: StringUuid) -> Self {
         match item {
+            StringUuid::BatteryPowerState => BATTERY_POWER_STATE_UUID,
             StringUuid::BodyLocation => BODY_LOCATION_UUID,
             StringUuid::ModelNumber => MODEL_NUMBER_STRING_UUID,
             StringUuid::ManufacturerName => MANUFACTURER_NAME_STRING_UUID,
@@ -74,6 +89,9 @@ impl From<StringUuid> for Uuid {
             StringUuid::SoftwareRevision => SOFTWARE_REVISION_STRING_UUID,
             StringUuid::SerialNumber => SERIAL_NUMBER_STRING_UUID,
             StringUuid::SystemId => SYSTEM_ID_UUID,
+            StringUuid::DeviceName => DEVICE_NAME_UUID,
+            StringUuid::CurrentTime => CURRENT_TIME_UUID,
+            StringUuid::HeartRateControlPoint => HEART_RATE_CONTROL_POINT_UUID,
         }
     }
 }
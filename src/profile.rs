@@ -0,0 +1,164 @@
+//! # Profile
+//!
+//! Profile contains [`DeviceProfile`], which builds the PMD control point commands for a model
+//! of Polar device, along with [`H10Profile`] for the Polar H10 and [`PolarModel`] to tell
+//! models apart.
+//!
+
+use crate::H10MeasurementType;
+
+use std::fmt;
+
+/// Model of a Polar device
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum PolarModel {
+    /// Polar H10 chest strap
+    H10,
+    /// Polar H9 chest strap
+    H9,
+    /// Polar OH1 optical arm band
+    OH1,
+    /// Polar Verity Sense optical arm band
+    VeritySense,
+    /// Any other model, holding the model name it reported
+    Unknown(String),
+}
+
+impl PolarModel {
+    /// Returns [`true`] if the model has a PMD service for measurement data
+    ///
+    /// [`PolarModel::Unknown`] models return [`false`], since nothing is known about them.
+    pub fn has_pmd(&self) -> bool {
+        matches!(
+            self,
+            PolarModel::H10 | PolarModel::OH1 | PolarModel::VeritySense
+        )
+    }
+}
+
+impl From<&str> for PolarModel {
+    /// Classify a model number (e.g, "H10") or the model part of an advertised name
+    ///
+    /// Matching ignores case and a leading "Polar".
+    fn from(model: &str) -> PolarModel {
+        let model = model.trim();
+        let name = model
+            .strip_prefix("Polar")
+            .unwrap_or(model)
+            .trim()
+            .to_ascii_uppercase();
+
+        match name.as_str() {
+            "H10" => PolarModel::H10,
+            "H9" => PolarModel::H9,
+            "OH1" | "OH1+" => PolarModel::OH1,
+            "VERITY SENSE" | "SENSE" => PolarModel::VeritySense,
+            _ => PolarModel::Unknown(model.to_string()),
+        }
+    }
+}
+
+/// Builds the PMD control point commands for one model of Polar device
+///
+/// Every device uses [`H10Profile`] by default. Implement this for devices whose commands
+/// differ from the H10's and set it with [`crate::PolarSensor::device_profile`].
+pub trait DeviceProfile: fmt::Debug + Send + Sync {
+    /// Command requesting the settings `ty` supports
+    fn settings_command(&self, ty: H10MeasurementType) -> Vec<u8> {
+        vec![0x01, ty.as_u8()]
+    }
+
+    /// Command starting `ty`
+    ///
+    /// The ACC `range` (in G), `sample_rate` (in Hz) and `resolution` (in bits) are the ones
+    /// set on the [`crate::PolarSensor`].
+    fn start_command(
+        &self,
+        ty: H10MeasurementType,
+        range: u8,
+        sample_rate: u8,
+        resolution: u8,
+    ) -> Vec<u8>;
+
+    /// Command stopping `ty`
+    fn stop_command(&self, ty: H10MeasurementType) -> Vec<u8> {
+        vec![0x03, ty.as_u8()]
+    }
+}
+
+/// Commands for the Polar H10
+///
+/// ECG always starts at 130 Hz with 14 bit resolution, which is the only setting it supports.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct H10Profile;
+
+impl DeviceProfile for H10Profile {
+    fn start_command(
+        &self,
+        ty: H10MeasurementType,
+        range: u8,
+        sample_rate: u8,
+        resolution: u8,
+    ) -> Vec<u8> {
+        let mut command = vec![0x02u8, ty.as_u8()];
+
+        // Each setting is [setting type, count, u16 value]
+        match ty {
+            H10MeasurementType::Acc => {
+                command.extend([0x02, 0x01, range, 0x00]);
+                command.extend([0x00, 0x01, sample_rate, 0x00]);
+                command.extend([0x01, 0x01, resolution, 0x00]);
+            }
+            H10MeasurementType::Ecg => {
+                command.extend([0x00, 0x01, 0x82, 0x00]);
+                command.extend([0x01, 0x01, 0x0e, 0x00]);
+            }
+        }
+
+        command
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn models() {
+        assert_eq!(PolarModel::from("H10"), PolarModel::H10);
+        assert_eq!(PolarModel::from("Polar H9"), PolarModel::H9);
+        assert_eq!(PolarModel::from("oh1 "), PolarModel::OH1);
+        assert_eq!(PolarModel::from("Verity Sense"), PolarModel::VeritySense);
+        assert_eq!(PolarModel::from("Polar Sense"), PolarModel::VeritySense);
+        assert_eq!(
+            PolarModel::from("Polar H7"),
+            PolarModel::Unknown("Polar H7".to_string())
+        );
+
+        assert!(PolarModel::H10.has_pmd());
+        assert!(!PolarModel::H9.has_pmd());
+        assert!(!PolarModel::from("H7").has_pmd());
+    }
+
+    #[test]
+    fn h10_commands() {
+        let profile = H10Profile;
+
+        assert_eq!(
+            profile.start_command(H10MeasurementType::Acc, 8, 200, 16),
+            vec![0x02, 0x02, 0x02, 0x01, 8, 0x00, 0x00, 0x01, 200, 0x00, 0x01, 0x01, 16, 0x00]
+        );
+        assert_eq!(
+            profile.start_command(H10MeasurementType::Ecg, 8, 200, 16),
+            vec![0x02, 0x00, 0x00, 0x01, 0x82, 0x00, 0x01, 0x01, 0x0e, 0x00]
+        );
+        assert_eq!(
+            profile.stop_command(H10MeasurementType::Acc),
+            vec![0x03, 0x02]
+        );
+        assert_eq!(
+            profile.settings_command(H10MeasurementType::Ecg),
+            vec![0x01, 0x00]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! # Quality
+//!
+//! Quality contains helpers to estimate the signal quality of ECG data, such as detecting when the
+//! strap loses contact with the skin.
+//!
+
+/// Signal quality of the ECG stream over one window of samples
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SignalQuality {
+    /// The signal stayed almost constant for a long run of samples
+    pub flat: bool,
+    /// The signal was pinned near its limits for a long run of samples
+    pub saturated: bool,
+}
+
+impl SignalQuality {
+    /// Returns [`true`] if the electrodes are likely not touching the skin properly
+    pub fn is_lead_off(&self) -> bool {
+        self.flat || self.saturated
+    }
+}
+
+/// Estimates [`SignalQuality`] from a stream of ECG samples
+///
+/// Samples are checked in windows, and a window is flagged when it contains a long run of
+/// flat or saturated samples, which usually means the strap needs adjusting.
+#[derive(Debug, Clone)]
+pub struct SignalQualityEstimator {
+    window: usize,
+    min_run: usize,
+    flat_tolerance: i32,
+    rail: i32,
+    count: usize,
+    flat_start: Option<i32>,
+    flat_run: usize,
+    saturated_run: usize,
+    flat: bool,
+    saturated: bool,
+}
+
+impl Default for SignalQualityEstimator {
+    // One second windows at the H10's 130 Hz ECG rate, flagging half second runs
+    fn default() -> Self {
+        SignalQualityEstimator::new(130, 65, 5, 20_000)
+    }
+}
+
+impl SignalQualityEstimator {
+    /// Create a new estimator
+    ///
+    /// - `window`: number of samples in each reported window
+    /// - `min_run`: number of consecutive samples needed to flag a window
+    /// - `flat_tolerance`: how far (in µV) samples can move and still be considered flat
+    /// - `rail`: magnitude (in µV) at or above which a sample is considered saturated
+    pub fn new(
+        window: usize,
+        min_run: usize,
+        flat_tolerance: i32,
+        rail: i32,
+    ) -> SignalQualityEstimator {
+        SignalQualityEstimator {
+            window: window.max(1),
+            min_run: min_run.max(1),
+            flat_tolerance,
+            rail,
+            count: 0,
+            flat_start: None,
+            flat_run: 0,
+            saturated_run: 0,
+            flat: false,
+            saturated: false,
+        }
+    }
+
+    /// Add an ECG sample (in µV), returning the quality of the window once it's full
+    pub fn push(&mut self, sample: i32) -> Option<SignalQuality> {
+        match self.flat_start {
+            Some(start) if (sample - start).abs() <= self.flat_tolerance => self.flat_run += 1,
+            _ => {
+                self.flat_start = Some(sample);
+                self.flat_run = 1;
+            }
+        }
+
+        if sample.abs() >= self.rail {
+            self.saturated_run += 1;
+        } else {
+            self.saturated_run = 0;
+        }
+
+        self.flat |= self.flat_run >= self.min_run;
+        self.saturated |= self.saturated_run >= self.min_run;
+
+        self.count += 1;
+        if self.count < self.window {
+            return None;
+        }
+
+        // Runs carry over into the next window, only the flags are reset
+        let quality = SignalQuality {
+            flat: self.flat,
+            saturated: self.saturated,
+        };
+        self.count = 0;
+        self.flat = false;
+        self.saturated = false;
+
+        Some(quality)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(estimator: &mut SignalQualityEstimator, samples: &[i32]) -> Vec<SignalQuality> {
+        samples.iter().filter_map(|s| estimator.push(*s)).collect()
+    }
+
+    #[test]
+    fn good_signal() {
+        let mut estimator = SignalQualityEstimator::new(10, 4, 2, 1000);
+        let samples = [0, 10, 50, 300, -200, 20, 5, -5, 15, 0];
+
+        let quality = run(&mut estimator, &samples);
+        assert_eq!(quality.len(), 1);
+        assert!(!quality[0].is_lead_off());
+    }
+
+    #[test]
+    fn flat_signal() {
+        let mut estimator = SignalQualityEstimator::new(10, 4, 2, 1000);
+        let samples = [0, 10, 50, 51, 50, 49, 52, 300, -200, 20];
+
+        let quality = run(&mut estimator, &samples);
+        assert!(quality[0].flat);
+        assert!(!quality[0].saturated);
+        assert!(quality[0].is_lead_off());
+    }
+
+    #[test]
+    fn saturated_signal() {
+        let mut estimator = SignalQualityEstimator::new(5, 3, 0, 1000);
+        let samples = [0, 1200, -1500, 1100, 4, 8, 0, 12, 20, 4];
+
+        let quality = run(&mut estimator, &samples);
+        assert_eq!(quality.len(), 2);
+        assert!(quality[0].saturated);
+        assert!(!quality[0].flat);
+        assert!(!quality[1].is_lead_off());
+    }
+}
@@ -3,67 +3,272 @@
 //! Response contains types related to PMD data responses. Structures to interpret this data are found here.
 //!
 
-use crate::{Error, H10MeasurementType, PolarResult};
-
-// Helper function to convert variant length byte arrays to i32 numbers
-fn bytes_to_data(data: &[u8], len: usize) -> i32 {
-    if len == 3 {
-        let mut buf = [0u8; 4];
-        buf[..len].copy_from_slice(&data[..len]);
-
-        // Check if most significant byte is negative and retain that negative
-        if (data[len - 1] & 0x80) > 0 {
-            for i in buf[len..].iter_mut() {
-                *i = 0xff;
+use crate::bytes::{i32_le, sfloat_le, sign_extend, u16_le, u64_le};
+use crate::{Error, H10MeasurementType, ParseError, PolarResult};
+
+use std::collections::VecDeque;
+
+/// Struct for receiving measurement type data on PMD data
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PmdRead {
+    data_type: H10MeasurementType,
+    time_stamp: u64,
+    frame_type: PmdFrameType,
+    data: Vec<PmdData>,
+}
+
+impl PmdRead {
+    /// Create new [`PmdRead`]
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PmdRead::validate`].
+    pub fn new(data_stream: Vec<u8>) -> PolarResult<PmdRead> {
+        PmdRead::try_from(data_stream.as_slice())
+    }
+
+    /// Check that a raw packet from the PMD data stream is valid, without parsing its samples
+    ///
+    /// A valid packet has a 10 byte header made up of the measurement type, an 8 byte
+    /// timestamp and the frame type, followed by a whole number of samples of the size the
+    /// measurement and frame type call for.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Parse`] if the header is cut short or the samples don't fill a
+    /// whole number of frames, and [`Error::InvalidData`] if the measurement or frame type is
+    /// unknown. Delta compressed frames can't be decoded yet, so they're
+    /// [`Error::InvalidData`] as well.
+    pub fn validate(raw: &[u8]) -> PolarResult<()> {
+        PmdRead::frame_length(raw).map(|_| ())
+    }
+
+    // Check a packet, returning its measurement type and the size of each sample
+    fn frame_length(raw: &[u8]) -> PolarResult<(H10MeasurementType, usize)> {
+        ParseError::check("measurement type", raw, 0, 1)?;
+        ParseError::check("timestamp", raw, 1, 8)?;
+        ParseError::check("frame type", raw, 9, 1)?;
+
+        let data_type = H10MeasurementType::try_from(raw[0]).map_err(|_| Error::InvalidData)?;
+        let frame_type = PmdFrameType::try_from(raw[9])?;
+        let frame_length = data_type.as_bytes(frame_type).ok_or(Error::InvalidData)? as usize;
+        let partial = raw[10..].len() % frame_length;
+        if partial != 0 {
+            ParseError::check("sample", raw, raw.len() - partial, frame_length)?;
+        }
+
+        Ok((data_type, frame_length))
+    }
+
+    // Apply `calibration` to every ACC sample in place
+    pub(crate) fn calibrate(&mut self, calibration: &AccCalibration) {
+        if *calibration == AccCalibration::default() {
+            return;
+        }
+
+        for sample in &mut self.data {
+            if let PmdData::Acc(acc) = sample {
+                *acc = acc.calibrated(calibration);
             }
         }
+    }
+
+    /// Create a [`PmdRead`] from already parsed parts, e.g. as the expected value in tests
+    ///
+    /// The frame type is set to the one the H10 uses for `data_type` (14 bit ECG or 16 bit ACC).
+    pub fn from_parts(
+        data_type: H10MeasurementType,
+        time_stamp: u64,
+        data: Vec<PmdData>,
+    ) -> PmdRead {
+        let frame_type = match data_type {
+            H10MeasurementType::Ecg => PmdFrameType::Raw(0),
+            H10MeasurementType::Acc => PmdFrameType::Raw(1),
+        };
+
+        PmdRead {
+            data_type,
+            time_stamp,
+            frame_type,
+            data,
+        }
+    }
+
+    /// Return data type of this data
+    pub fn data_type(&self) -> &H10MeasurementType {
+        &self.data_type
+    }
+
+    /// Return timestamp of this data
+    pub fn time_stamp(&self) -> u64 {
+        self.time_stamp
+    }
 
-        i32::from_le_bytes(buf)
-    } else if len == 2 {
-        let mut buf = [0u8; 2];
-        buf[..len].copy_from_slice(&data[..len]);
+    /// Get the 10 byte header the packet started with: the measurement type, the timestamp
+    /// and the frame type
+    ///
+    /// Handy to attach to a bug report when a packet seems to be parsed wrong.
+    pub fn raw_header(&self) -> [u8; 10] {
+        let mut header = [0; 10];
+        header[0] = self.data_type.as_u8();
+        header[1..9].copy_from_slice(&self.time_stamp.to_le_bytes());
+        header[9] = self.frame_type.as_u8();
+        header
+    }
+
+    /// Get the frame type the samples were packed with
+    pub fn frame_type(&self) -> PmdFrameType {
+        self.frame_type
+    }
 
-        if (data[len - 1] & 0x80) > 0 {
-            for i in buf[len..].iter_mut() {
-                *i = 0xff;
+    /// Consumes self and returns all data
+    pub fn data(self) -> Vec<PmdData> {
+        self.data
+    }
+
+    /// Consumes self and returns all acceleration data, or [`None`] if this isn't ACC data
+    pub fn into_acc(self) -> Option<Vec<Acc>> {
+        if self.data_type != H10MeasurementType::Acc {
+            return None;
+        }
+
+        Some(
+            self.data
+                .into_iter()
+                .filter_map(|sample| match sample {
+                    PmdData::Acc(acc) => Some(acc),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Consumes self and returns all ECG data, or [`None`] if this isn't ECG data
+    pub fn into_ecg(self) -> Option<Vec<Ecg>> {
+        if self.data_type != H10MeasurementType::Ecg {
+            return None;
+        }
+
+        Some(
+            self.data
+                .into_iter()
+                .filter_map(|sample| match sample {
+                    PmdData::Ecg(ecg) => Some(ecg),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Consumes self and returns all acceleration data with the timestamp of each sample,
+    /// or [`None`] if this isn't ACC data
+    ///
+    /// The packet timestamp (in ns) belongs to the last sample, earlier samples are spaced
+    /// `1 / sample_rate` seconds apart before it.
+    pub fn into_timed_acc(self, sample_rate: u16) -> Option<Vec<(u64, Acc)>> {
+        let times = self.sample_times(sample_rate);
+        self.into_acc()
+            .map(|acc| times.into_iter().zip(acc).collect())
+    }
+
+    /// Consumes self and returns all ECG data with the timestamp of each sample,
+    /// or [`None`] if this isn't ECG data
+    ///
+    /// The packet timestamp (in ns) belongs to the last sample, earlier samples are spaced
+    /// `1 / sample_rate` seconds apart before it.
+    pub fn into_timed_ecg(self, sample_rate: u16) -> Option<Vec<(u64, Ecg)>> {
+        let times = self.sample_times(sample_rate);
+        self.into_ecg()
+            .map(|ecg| times.into_iter().zip(ecg).collect())
+    }
+
+    /// Estimate the sample rate (in Hz) from the packet received before this one
+    ///
+    /// Timestamps mark the last sample of each packet, so the samples in this packet were taken
+    /// between the two timestamps. Returns [`None`] if `previous` is a different measurement
+    /// type, isn't older than this packet or this packet has no samples.
+    pub fn implied_sample_rate(&self, previous: &PmdRead) -> Option<f64> {
+        if self.data_type != previous.data_type || self.data.is_empty() {
+            return None;
+        }
+
+        let elapsed = self.time_stamp.checked_sub(previous.time_stamp)?;
+        if elapsed == 0 {
+            return None;
+        }
+
+        Some(self.data.len() as f64 * 1e9 / elapsed as f64)
+    }
+
+    // Keep every `factor`th sample, carrying `phase` over from the previous packet. The
+    // timestamp is moved back to the last sample that was kept.
+    pub(crate) fn decimate(&mut self, factor: usize, phase: &mut usize, sample_rate: u16) {
+        if factor <= 1 {
+            return;
+        }
+
+        let times = self.sample_times(sample_rate);
+        let count = self.data.len();
+        let mut kept = vec![];
+        let mut last_time = None;
+        for (i, (sample, time)) in self.data.drain(..).zip(times).enumerate() {
+            if (*phase + i).is_multiple_of(factor) {
+                kept.push(sample);
+                last_time = Some(time);
             }
         }
 
-        let small = i16::from_le_bytes(buf);
-        i32::from(small)
-    } else {
-        let mut buf = [0u8; 1];
-        buf[..len].copy_from_slice(&data[..len]);
+        *phase = (*phase + count) % factor;
+        self.data = kept;
+        if let Some(time) = last_time {
+            self.time_stamp = time;
+        }
+    }
 
-        let small = i8::from_le_bytes(buf);
-        i32::from(small)
+    // Timestamp of each sample, counting back from the packet timestamp of the last one
+    fn sample_times(&self, sample_rate: u16) -> Vec<u64> {
+        let step = match sample_rate {
+            0 => 0,
+            rate => 1_000_000_000 / u64::from(rate),
+        };
+        let count = self.data.len() as u64;
+
+        (1..=count)
+            .map(|i| self.time_stamp.saturating_sub((count - i) * step))
+            .collect()
+    }
+
+    // Borrow all data without consuming self
+    pub(crate) fn samples(&self) -> &[PmdData] {
+        &self.data
     }
 }
 
-/// Struct for receiving measurement type data on PMD data
-#[derive(Debug)]
-pub struct PmdRead {
-    data_type: H10MeasurementType,
-    time_stamp: u64,
-    data: Vec<PmdData>,
+impl TryFrom<&[u8]> for PmdRead {
+    type Error = Error;
+
+    /// Parse a PMD data packet without taking ownership of it
+    fn try_from(data_stream: &[u8]) -> PolarResult<PmdRead> {
+        PmdRead::with_ecg_resolution(data_stream, 24)
+    }
 }
 
 impl PmdRead {
-    /// Create new [`PmdRead`]
-    pub fn new(data_stream: Vec<u8>) -> PolarResult<PmdRead> {
-        let data_type = H10MeasurementType::try_from(data_stream[0]);
-        if let Err(_e) = data_type {
-            return Err(Error::InvalidData);
-        }
-        let data_type = data_type.unwrap();
-        let time_stamp = u64::from_le_bytes(
-            data_stream[1..9]
-                .try_into()
-                .expect("Timestamp slice could not be converted to u64"),
-        );
+    /// Parse a PMD data packet, reading ECG samples as `ecg_resolution` bit values
+    ///
+    /// Use the resolution from the device's ECG [`crate::StreamSettings`] when it reports
+    /// something other than 14 bits, see [`crate::PolarSensor::ecg_resolution`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`PmdRead::validate`].
+    pub fn with_ecg_resolution(data_stream: &[u8], ecg_resolution: u8) -> PolarResult<PmdRead> {
+        let (data_type, frame_length) = PmdRead::frame_length(data_stream)?;
+        let time_stamp = u64_le(&data_stream[1..9]);
 
-        // Read all samples from data stream
-        let frame_length = data_type.as_bytes() as usize;
+        // Read all samples from data stream, the frame type tells us the resolution
         let samples = data_stream[10..].len() / frame_length;
         let mut data: Vec<PmdData> = Vec::new();
         let mut current_pos = 10;
@@ -71,10 +276,11 @@ impl PmdRead {
         for _ in 0..samples {
             data.push(match data_type {
                 H10MeasurementType::Ecg => PmdData::Ecg(Ecg::new(
-                    &data_stream[current_pos..current_pos + frame_length].to_vec(),
+                    &data_stream[current_pos..current_pos + frame_length],
+                    ecg_resolution,
                 )?),
                 H10MeasurementType::Acc => PmdData::Acc(Acc::new(
-                    &data_stream[current_pos..current_pos + frame_length].to_vec(),
+                    &data_stream[current_pos..current_pos + frame_length],
                 )?),
             });
             current_pos += frame_length;
@@ -83,28 +289,256 @@ impl PmdRead {
         Ok(PmdRead {
             data_type,
             time_stamp,
+            frame_type: PmdFrameType::try_from(data_stream[9])?,
             data,
         })
     }
+}
 
-    /// Return data type of this data
-    pub fn data_type(&self) -> &H10MeasurementType {
-        &self.data_type
+/// Frame type of a PMD data packet, which says how its samples are packed
+///
+/// The low 7 bits hold the frame type number, and the top bit is set when the samples are
+/// delta compressed.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PmdFrameType {
+    /// Samples are stored one after another, with the frame type number (e.g, the ACC
+    /// resolution) picking their size
+    Raw(u8),
+    /// Samples are delta compressed against a reference sample
+    Delta(u8),
+}
+
+// Highest frame type number the PMD specification defines
+const MAX_FRAME_TYPE: u8 = 10;
+
+impl TryFrom<u8> for PmdFrameType {
+    type Error = Error;
+
+    /// Returns [`Error::InvalidData`] if the frame type number isn't one the PMD service defines
+    fn try_from(val: u8) -> PolarResult<PmdFrameType> {
+        let number = val & 0x7f;
+        if number > MAX_FRAME_TYPE {
+            return Err(Error::InvalidData);
+        }
+
+        if val & 0x80 != 0 {
+            Ok(PmdFrameType::Delta(number))
+        } else {
+            Ok(PmdFrameType::Raw(number))
+        }
     }
+}
 
-    /// Return timestamp of this data
-    pub fn time_stamp(&self) -> u64 {
-        self.time_stamp
+impl PmdFrameType {
+    /// Get the frame type number, without the delta compression flag
+    pub fn number(&self) -> u8 {
+        match *self {
+            PmdFrameType::Raw(number) | PmdFrameType::Delta(number) => number,
+        }
     }
 
-    /// Consumes self and returns all data
-    pub fn data(self) -> Vec<PmdData> {
-        self.data
+    /// Returns [`true`] if the samples are delta compressed
+    pub fn is_delta(&self) -> bool {
+        matches!(self, PmdFrameType::Delta(_))
+    }
+
+    /// Get the frame type byte as it's sent in the packet header
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            PmdFrameType::Raw(number) => number,
+            PmdFrameType::Delta(number) => number | 0x80,
+        }
+    }
+}
+
+// Ring buffer of the most recent samples of each type
+#[derive(Debug, Default)]
+pub(crate) struct SampleHistory {
+    capacity: usize,
+    acc: VecDeque<Acc>,
+    ecg: VecDeque<Ecg>,
+}
+
+impl SampleHistory {
+    // Change how many samples of each type are kept, 0 disables the history
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.acc.len() > capacity {
+            self.acc.pop_front();
+        }
+        while self.ecg.len() > capacity {
+            self.ecg.pop_front();
+        }
+    }
+
+    pub(crate) fn push(&mut self, read: &PmdRead) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        for sample in read.samples() {
+            match sample {
+                PmdData::Acc(acc) => {
+                    if self.acc.len() == self.capacity {
+                        self.acc.pop_front();
+                    }
+                    self.acc.push_back(acc.clone());
+                }
+                PmdData::Ecg(ecg) => {
+                    if self.ecg.len() == self.capacity {
+                        self.ecg.pop_front();
+                    }
+                    self.ecg.push_back(ecg.clone());
+                }
+            }
+        }
+    }
+
+    // Last `n` acceleration samples, oldest first
+    pub(crate) fn recent_acc(&self, n: usize) -> Vec<Acc> {
+        self.acc
+            .iter()
+            .skip(self.acc.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
+    // Last `n` ECG samples, oldest first
+    pub(crate) fn recent_ecg(&self, n: usize) -> Vec<Ecg> {
+        self.ecg
+            .iter()
+            .skip(self.ecg.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Measures the duration of a PMD timestamp tick using consecutive packets
+///
+/// Timestamps are assumed to be nanoseconds, but this can be confirmed per device by
+/// comparing the timestamp difference of two packets with the time their samples
+/// should take at a known sample rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampCalibration {
+    sample_rate: u16,
+    last_time_stamp: Option<u64>,
+    tick_ns: Option<f64>,
+}
+
+impl TimestampCalibration {
+    /// Create a new calibration for a stream running at `sample_rate` Hz
+    pub fn new(sample_rate: u16) -> TimestampCalibration {
+        TimestampCalibration {
+            sample_rate,
+            last_time_stamp: None,
+            tick_ns: None,
+        }
+    }
+
+    /// Record the next packet of the stream, returning the measured tick duration if known
+    pub fn update(&mut self, read: &PmdRead) -> Option<f64> {
+        if let Some(last) = self.last_time_stamp {
+            // the timestamp marks the last sample, so the delta covers this packet's samples
+            if read.time_stamp > last && !read.data.is_empty() && self.sample_rate != 0 {
+                let elapsed_ns = read.data.len() as f64 * 1e9 / f64::from(self.sample_rate);
+                self.tick_ns = Some(elapsed_ns / (read.time_stamp - last) as f64);
+            }
+        }
+        self.last_time_stamp = Some(read.time_stamp);
+
+        self.tick_ns
+    }
+
+    /// Get the measured duration of one timestamp tick (in ns)
+    pub fn tick_ns(&self) -> Option<f64> {
+        self.tick_ns
+    }
+
+    /// Convert a device timestamp to nanoseconds using the measured tick duration
+    pub fn to_nanos(&self, time_stamp: u64) -> Option<u64> {
+        self.tick_ns.map(|tick| (time_stamp as f64 * tick) as u64)
+    }
+}
+
+/// Samples missing between two consecutive PMD packets
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TimestampGap {
+    /// Timestamp of the packet received before the gap
+    pub previous_time_stamp: u64,
+    /// Timestamp of the packet received after the gap
+    pub time_stamp: u64,
+    /// Estimated number of samples that were lost
+    pub missing_samples: u64,
+}
+
+/// Detects dropped packets in a stream using the device timestamps
+///
+/// Each packet's timestamp marks its last sample, so the difference between two consecutive
+/// timestamps should match the time taken by the samples in the newer packet. Anything more
+/// than half a sample period beyond that is reported as a [`TimestampGap`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampTracker {
+    sample_rate: u16,
+    tick_ns: f64,
+    last_time_stamp: Option<u64>,
+    dropped_samples: u64,
+}
+
+impl TimestampTracker {
+    /// Create a new tracker for a stream running at `sample_rate` Hz, with nanosecond timestamps
+    pub fn new(sample_rate: u16) -> TimestampTracker {
+        TimestampTracker {
+            sample_rate,
+            tick_ns: 1.0,
+            last_time_stamp: None,
+            dropped_samples: 0,
+        }
+    }
+
+    /// Use a measured timestamp tick duration, see [`TimestampCalibration`]
+    pub fn with_tick_ns(mut self, tick_ns: f64) -> TimestampTracker {
+        self.tick_ns = tick_ns;
+        self
+    }
+
+    /// Record the next packet of the stream, returning a gap if samples were lost before it
+    pub fn update(&mut self, read: &PmdRead) -> Option<TimestampGap> {
+        let last = self.last_time_stamp.replace(read.time_stamp)?;
+        if read.time_stamp <= last || self.sample_rate == 0 {
+            return None;
+        }
+
+        let period_ns = 1e9 / f64::from(self.sample_rate);
+        let elapsed_ns = (read.time_stamp - last) as f64 * self.tick_ns;
+        let expected_ns = read.data.len() as f64 * period_ns;
+        let missing = ((elapsed_ns - expected_ns) / period_ns).round();
+
+        if missing < 1.0 {
+            return None;
+        }
+
+        let missing_samples = missing as u64;
+        self.dropped_samples += missing_samples;
+
+        Some(TimestampGap {
+            previous_time_stamp: last,
+            time_stamp: read.time_stamp,
+            missing_samples,
+        })
+    }
+
+    /// Get the estimated number of samples lost so far
+    pub fn dropped_samples(&self) -> u64 {
+        self.dropped_samples
     }
 }
 
 /// Enum to store which kind of data was received
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum PmdData {
     /// Electrocardiogram
     Ecg(Ecg),
@@ -112,35 +546,71 @@ pub enum PmdData {
     Acc(Acc),
 }
 
+impl PmdData {
+    /// Return the measurement type of this data
+    pub fn kind(&self) -> H10MeasurementType {
+        match self {
+            PmdData::Ecg(_) => H10MeasurementType::Ecg,
+            PmdData::Acc(_) => H10MeasurementType::Acc,
+        }
+    }
+}
+
 /// Struct to store ECG from the PMD data stream
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Ecg {
     val: i32,
 }
 
 impl Ecg {
-    /// Create new instance of [`Ecg`]
-    fn new(data: &Vec<u8>) -> PolarResult<Ecg> {
-        if data.len() < 3 {
-            eprintln!("ECG expects 3 bytes of data, got {}.", data.len());
-            return Err(Error::InvalidLength);
-        }
-        let mut mag = [0u8; 4];
-        mag[1..4].clone_from_slice(&data[..3]);
+    /// Create new instance of [`Ecg`] from a 3 byte frame holding a `resolution` bit value
+    ///
+    /// Values are sign extended from the top bit of `resolution` for resolutions of 17 to 23
+    /// bits. Smaller resolutions (such as the H10's 14 bits) already arrive sign extended
+    /// to 24 bits, so they're read the same as 24 bit values.
+    fn new(data: &[u8], resolution: u8) -> PolarResult<Ecg> {
+        ParseError::check("ECG sample", data, 0, 3)?;
 
-        let val = bytes_to_data(&data[..3], 3);
+        let val = i32_le(&data[..3]);
+        if (17..24).contains(&resolution) {
+            return Ok(Ecg {
+                val: sign_extend(val, resolution),
+            });
+        }
 
         Ok(Ecg { val })
     }
 
+    /// Create an [`Ecg`] sample from a value (in µV)
+    pub fn from_val(val: i32) -> Ecg {
+        Ecg { val }
+    }
+
     /// Return ECG value (in µV)
     pub fn val(&self) -> &i32 {
         &self.val
     }
 }
 
+/// Named x, y and z values of a three axis measurement
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Axes3 {
+    /// X axis
+    pub x: i32,
+    /// Y axis
+    pub y: i32,
+    /// Z axis
+    pub z: i32,
+}
+
 /// Struct to store acceleration from the PMD data stream
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Acc {
     x: i32,
     y: i32,
@@ -149,59 +619,264 @@ pub struct Acc {
 
 impl Acc {
     /// Create new instance of [`Acc`]
-    fn new(data: &Vec<u8>) -> PolarResult<Acc> {
-        if data.len() < 2 {
-            eprintln!("Acceleration expects 2 bytes of data, got {}", data.len());
-            return Err(Error::InvalidLength);
+    ///
+    /// Each axis takes up a third of `data`, which is 3, 6 or 9 bytes depending on resolution
+    fn new(data: &[u8]) -> PolarResult<Acc> {
+        if !matches!(data.len(), 3 | 6 | 9) {
+            return Err(Error::Parse(ParseError {
+                context: "ACC sample",
+                offset: 0,
+                len: (data.len().div_ceil(3) * 3).clamp(3, 9),
+                got: data.len(),
+            }));
         }
-        let frame_size = 6;
+        let axis = data.len() / 3;
 
         Ok(Acc {
-            x: bytes_to_data(&data[..frame_size / 3], frame_size / 3),
-            y: bytes_to_data(&data[frame_size / 3..(frame_size / 3) * 2], frame_size / 3),
-            z: bytes_to_data(
-                &data[(frame_size / 3) * 2..(frame_size / 3) * 3],
-                frame_size / 3,
-            ),
+            x: i32_le(&data[..axis]),
+            y: i32_le(&data[axis..axis * 2]),
+            z: i32_le(&data[axis * 2..axis * 3]),
         })
     }
 
+    /// Create an [`Acc`] sample from named axes (in mG)
+    pub fn from_xyz(axes: Axes3) -> Acc {
+        Acc {
+            x: axes.x,
+            y: axes.y,
+            z: axes.z,
+        }
+    }
+
     /// Return data as a tuple (in mG)
     pub fn data(&self) -> (i32, i32, i32) {
         (self.x, self.y, self.z)
     }
+
+    /// Return data with named axes (in mG)
+    pub fn xyz(&self) -> Axes3 {
+        Axes3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
+    }
+
+    /// Return data as a tuple (in m/s²)
+    ///
+    /// The H10 reports samples in mG whichever range is set, so the range doesn't change
+    /// the conversion.
+    pub fn accel_ms2(&self) -> (f32, f32, f32) {
+        // Standard gravity in m/s² per mG
+        const MS2_PER_MG: f32 = 9.80665 / 1000.0;
+
+        (
+            self.x as f32 * MS2_PER_MG,
+            self.y as f32 * MS2_PER_MG,
+            self.z as f32 * MS2_PER_MG,
+        )
+    }
+
+    /// Return this sample with `calibration` applied
+    pub fn calibrated(&self, calibration: &AccCalibration) -> Acc {
+        let apply = |val: i32, off: i32| {
+            ((f64::from(val) - f64::from(off)) * calibration.scale).round() as i32
+        };
+
+        Acc {
+            x: apply(self.x, calibration.x_off),
+            y: apply(self.y, calibration.y_off),
+            z: apply(self.z, calibration.z_off),
+        }
+    }
+}
+
+/// Offsets and scale used to correct ACC samples, see [`crate::PolarSensor::acc_calibration`]
+///
+/// Each axis is corrected as `(raw - offset) * scale`. The default has no offset and unit
+/// scale, which leaves samples unchanged.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AccCalibration {
+    /// Offset subtracted from the X axis (in mG)
+    pub x_off: i32,
+    /// Offset subtracted from the Y axis (in mG)
+    pub y_off: i32,
+    /// Offset subtracted from the Z axis (in mG)
+    pub z_off: i32,
+    /// Scale applied to every axis after the offset
+    pub scale: f64,
+}
+
+impl Default for AccCalibration {
+    fn default() -> Self {
+        AccCalibration {
+            x_off: 0,
+            y_off: 0,
+            z_off: 0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Battery level along with whether the device is charging
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Battery {
+    /// Battery level in percent
+    pub level: u8,
+    /// Whether the battery is charging, or [`None`] if the device doesn't report it
+    pub charging: Option<bool>,
+}
+
+impl Battery {
+    /// Get the charging state from a battery power state byte
+    ///
+    /// Bits 4 and 5 hold the charging state, where 2 is charging, 3 is not charging, and
+    /// anything else means unknown or unsupported.
+    pub(crate) fn charging_from_power_state(state: u8) -> Option<bool> {
+        match (state >> 4) & 0b11 {
+            0b10 => Some(true),
+            0b11 => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// SpO2 measurement from the standard Pulse Oximeter service
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Spo2 {
+    spo2: Option<f32>,
+    pulse_rate: Option<f32>,
+}
+
+impl Spo2 {
+    /// Get the oxygen saturation (in %), or [`None`] if the device couldn't measure it
+    pub fn spo2(&self) -> Option<f32> {
+        self.spo2
+    }
+
+    /// Get the pulse rate (in BPM), or [`None`] if the device couldn't measure it
+    pub fn pulse_rate(&self) -> Option<f32> {
+        self.pulse_rate
+    }
+}
+
+impl TryFrom<&[u8]> for Spo2 {
+    type Error = Error;
+
+    /// Parse a PLX continuous measurement, reading only the normal SpO2 and pulse rate
+    ///
+    /// The optional fields that can follow (fast and slow readings, status and pulse
+    /// amplitude) are skipped.
+    fn try_from(data: &[u8]) -> PolarResult<Spo2> {
+        // Flags followed by two SFLOATs
+        ParseError::check("SpO2 measurement", data, 0, 5)?;
+
+        Ok(Spo2 {
+            spo2: sfloat_le(&data[1..3]),
+            pulse_rate: sfloat_le(&data[3..5]),
+        })
+    }
 }
 
+// Longest RR interval accepted (in ms), anything longer (20 bpm) comes from a corrupt packet
+const MAX_RR_MS: u32 = 3000;
+
 /// Structure to contain HR data and RR interval
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct HeartRate {
+    flags: u8,
     bpm: u8,
     rr: Option<Vec<u16>>,
+    rr_present: bool,
+    contact: Option<bool>,
 }
 
 impl HeartRate {
     /// Create new instance of [`HeartRate`]
     pub fn new(data: Vec<u8>) -> PolarResult<HeartRate> {
-        if data.len() < 2 {
-            eprintln!(
-                "Heart rate expects atleast 2 bytes of data, got {}",
-                data.len()
-            );
-            return Err(Error::InvalidLength);
+        HeartRate::try_from(data.as_slice())
+    }
+
+    /// Get BPM of heart rate measurement
+    ///
+    /// This is 0 when the strap has lost contact, see [`HeartRate::valid_bpm`].
+    pub fn bpm(&self) -> &u8 {
+        &self.bpm
+    }
+
+    /// Get BPM of heart rate measurement, or [`None`] if the sensor has lost contact with the skin
+    pub fn valid_bpm(&self) -> Option<u8> {
+        if self.contact_lost() {
+            return None;
         }
+
+        Some(self.bpm)
+    }
+
+    /// Returns whether the sensor reported skin contact, or [`None`] if it can't detect contact
+    pub fn sensor_contact(&self) -> Option<bool> {
+        self.contact
+    }
+
+    /// Returns [`true`] if the sensor can detect contact and reported that it has none
+    pub fn contact_lost(&self) -> bool {
+        self.contact == Some(false)
+    }
+
+    /// Get the raw flags byte the measurement started with
+    ///
+    /// Useful for flags this crate doesn't interpret, the ones it does are available through
+    /// [`HeartRate::sensor_contact`] and [`HeartRate::rr_present_flag`].
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns whether the packet had the RR interval flag set
+    ///
+    /// [`HeartRate::rr`] is [`None`] both when this flag isn't set and when it is set
+    /// but the packet held no intervals, which this can tell apart.
+    pub fn rr_present_flag(&self) -> bool {
+        self.rr_present
+    }
+
+    /// Get RR interval as a tuple
+    pub fn rr(&self) -> &Option<Vec<u16>> {
+        &self.rr
+    }
+
+    /// Get RR intervals as a slice, empty if there are none
+    pub fn rr_slice(&self) -> &[u16] {
+        self.rr.as_deref().unwrap_or(&[])
+    }
+}
+
+impl TryFrom<&[u8]> for HeartRate {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> PolarResult<HeartRate> {
+        ParseError::check("heart rate measurement", data, 0, 2)?;
         let flags = data[0];
-        let samples = if flags & 0b00010000 == 16 {
-            (data.len() - 2) / 2
-        } else {
-            0
-        };
+        let rr_present = flags & 0b00010000 == 16;
+        // Contact is only reported when the sensor supports detecting it
+        let contact = (flags & 0b00000100 != 0).then_some(flags & 0b00000010 != 0);
+        let samples = if rr_present { (data.len() - 2) / 2 } else { 0 };
 
         let bpm = data[1];
         let mut rr_samp = vec![];
 
         for i in 0..samples {
             // rr values are stored as 1024ths of a second, convert to ms
-            rr_samp.push(((bytes_to_data(&data[i * 2 + 2..i * 2 + 4], 2) as u32 * 128) / 125) as u16);
+            let rr_ms = u32::from(u16_le(&data[i * 2 + 2..])) * 128 / 125;
+            // Drop implausible intervals, the heart rate in the same packet is still good
+            if rr_ms <= MAX_RR_MS {
+                rr_samp.push(rr_ms as u16);
+            }
         }
 
         let rr = if !rr_samp.is_empty() {
@@ -210,17 +885,13 @@ impl HeartRate {
             None
         };
 
-        Ok(HeartRate { bpm, rr })
-    }
-
-    /// Get BPM of heart rate measurement
-    pub fn bpm(&self) -> &u8 {
-        &self.bpm
-    }
-
-    /// Get RR interval as a tuple
-    pub fn rr(&self) -> &Option<Vec<u16>> {
-        &self.rr
+        Ok(HeartRate {
+            flags,
+            bpm,
+            rr,
+            rr_present,
+            contact,
+        })
     }
 }
 
@@ -240,17 +911,306 @@ mod test {
         assert_eq!(*response.data_type(), H10MeasurementType::Acc);
         assert_eq!(response.time_stamp(), 599618164814402794u64);
         let the_data = response.data();
+        assert_eq!(the_data[0].kind(), H10MeasurementType::Acc);
         match &the_data[0] {
             PmdData::Acc(thing) => {
                 let (x, y, z) = thing.data();
                 assert_eq!(x, -187);
                 assert_eq!(y, -28);
                 assert_eq!(z, 949);
+                assert_eq!(
+                    thing.xyz(),
+                    Axes3 {
+                        x: -187,
+                        y: -28,
+                        z: 949
+                    }
+                );
             }
             _ => panic!("Instantiated object of wrong type, expected Acc"),
         }
     }
 
+    #[test]
+    fn pmd_read_into_typed() {
+        let packet = vec![
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff, 0x01,
+            0x00, 0x00,
+        ];
+
+        let ecg = PmdRead::new(packet.clone()).unwrap().into_ecg().unwrap();
+        assert_eq!(ecg, vec![Ecg::from_val(-1), Ecg::from_val(1)]);
+        assert!(PmdRead::new(packet).unwrap().into_acc().is_none());
+    }
+
+    #[test]
+    fn pmd_read_acc_8_bit() {
+        let response = PmdRead::new(vec![
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xfe, 0x10, 0x7f, 0x80,
+            0x00, 0x01,
+        ])
+        .unwrap();
+
+        let axes = |x, y, z| Acc::from_xyz(Axes3 { x, y, z });
+        assert_eq!(*response.data_type(), H10MeasurementType::Acc);
+        assert_eq!(response.time_stamp(), 599618164814402794u64);
+        assert_eq!(response.frame_type(), PmdFrameType::Raw(0));
+        assert_eq!(
+            response.into_acc().unwrap(),
+            vec![axes(-2, 16, 127), axes(-128, 0, 1)]
+        );
+    }
+
+    #[test]
+    fn pmd_read_acc_24_bit() {
+        let response = PmdRead::new(vec![
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x02, 0x45, 0xff, 0xff, 0xe4,
+            0xff, 0xff, 0xb5, 0x03, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(*response.data_type(), H10MeasurementType::Acc);
+        assert_eq!(response.time_stamp(), 599618164814402794u64);
+        assert_eq!(response.frame_type(), PmdFrameType::Raw(2));
+        assert_eq!(
+            response.into_acc().unwrap(),
+            vec![Acc::from_xyz(Axes3 {
+                x: -187,
+                y: -28,
+                z: 949
+            })]
+        );
+    }
+
+    #[test]
+    fn pmd_read_raw_header() {
+        let raw = [
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x02, 0x45, 0xff, 0xff, 0xe4,
+            0xff, 0xff, 0xb5, 0x03, 0x00,
+        ];
+        let response = PmdRead::new(raw.to_vec()).unwrap();
+        assert_eq!(response.raw_header(), raw[..10]);
+
+        let ecg = PmdRead::from_parts(H10MeasurementType::Ecg, 1, vec![]);
+        assert_eq!(ecg.raw_header(), [0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn pmd_frame_types() {
+        assert_eq!(PmdFrameType::try_from(0x01).unwrap(), PmdFrameType::Raw(1));
+        assert_eq!(
+            PmdFrameType::try_from(0x82).unwrap(),
+            PmdFrameType::Delta(2)
+        );
+        assert!(PmdFrameType::try_from(0x0b).is_err());
+        for val in [0x00, 0x0a, 0x80, 0x8a] {
+            assert_eq!(PmdFrameType::try_from(val).unwrap().as_u8(), val);
+        }
+        assert!(PmdFrameType::Delta(0).is_delta());
+        assert_eq!(PmdFrameType::Delta(1).number(), 1);
+
+        let acc = [
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x01, 0x45, 0xff, 0xe4, 0xff,
+            0xb5, 0x03,
+        ];
+        assert_eq!(
+            PmdRead::new(acc.to_vec()).unwrap().frame_type(),
+            PmdFrameType::Raw(1)
+        );
+
+        // Delta compressed frames aren't decoded yet
+        let mut delta = acc;
+        delta[9] = 0x81;
+        assert!(matches!(
+            PmdRead::new(delta.to_vec()),
+            Err(Error::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn pmd_read_acc_bad_frame_type() {
+        assert!(PmdRead::new(vec![
+            0x02, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x05, 0x45, 0xff, 0xe4,
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn pmd_read_implied_rate() {
+        let ecg = |time_stamp, count| {
+            PmdRead::from_parts(
+                H10MeasurementType::Ecg,
+                time_stamp,
+                vec![PmdData::Ecg(Ecg::from_val(0)); count],
+            )
+        };
+
+        let first = ecg(1_000_000_000, 73);
+        let second = ecg(1_500_000_000, 65);
+        assert_eq!(second.implied_sample_rate(&first), Some(130.0));
+
+        assert_eq!(first.implied_sample_rate(&second), None);
+        assert_eq!(first.implied_sample_rate(&first), None);
+        assert_eq!(ecg(1_500_000_000, 0).implied_sample_rate(&first), None);
+
+        let acc = PmdRead::from_parts(H10MeasurementType::Acc, 1_000_000_000, vec![]);
+        assert_eq!(second.implied_sample_rate(&acc), None);
+    }
+
+    #[test]
+    fn pmd_read_timed() {
+        let read = PmdRead::from_parts(
+            H10MeasurementType::Ecg,
+            10_000_000_000,
+            vec![
+                PmdData::Ecg(Ecg::from_val(1)),
+                PmdData::Ecg(Ecg::from_val(2)),
+                PmdData::Ecg(Ecg::from_val(3)),
+            ],
+        );
+
+        assert!(read.clone().into_timed_acc(130).is_none());
+        assert_eq!(
+            read.clone().into_timed_ecg(100).unwrap(),
+            vec![
+                (9_980_000_000, Ecg::from_val(1)),
+                (9_990_000_000, Ecg::from_val(2)),
+                (10_000_000_000, Ecg::from_val(3)),
+            ]
+        );
+        assert!(read
+            .into_timed_ecg(0)
+            .unwrap()
+            .iter()
+            .all(|(time, _)| *time == 10_000_000_000));
+
+        let empty = PmdRead::from_parts(H10MeasurementType::Acc, 5, vec![]);
+        assert_eq!(empty.into_timed_acc(200).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn pmd_read_decimate() {
+        let read = |vals: &[i32]| {
+            PmdRead::from_parts(
+                H10MeasurementType::Ecg,
+                1_000_000_000,
+                vals.iter()
+                    .map(|v| PmdData::Ecg(Ecg::from_val(*v)))
+                    .collect(),
+            )
+        };
+        let mut phase = 0;
+
+        // Keeps samples 0 and 3, the timestamp moves back one sample to sample 3
+        let mut first = read(&[0, 1, 2, 3, 4]);
+        first.decimate(3, &mut phase, 100);
+        assert_eq!(
+            first.clone().into_ecg().unwrap(),
+            vec![Ecg::from_val(0), Ecg::from_val(3)]
+        );
+        assert_eq!(first.time_stamp(), 990_000_000);
+        assert_eq!(phase, 2);
+
+        // Carries on from the previous packet, keeping the 2nd sample
+        let mut second = read(&[5, 6, 7]);
+        second.decimate(3, &mut phase, 100);
+        assert_eq!(second.clone().into_ecg().unwrap(), vec![Ecg::from_val(6)]);
+        assert_eq!(second.time_stamp(), 990_000_000);
+
+        let mut unchanged = read(&[1, 2]);
+        unchanged.decimate(1, &mut phase, 100);
+        assert_eq!(unchanged, read(&[1, 2]));
+    }
+
+    #[test]
+    fn pmd_read_validate() {
+        let header = [0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00];
+        assert!(PmdRead::validate(&header).is_ok());
+        assert!(PmdRead::validate(&[header.as_slice(), &[0xff; 6]].concat()).is_ok());
+
+        assert!(matches!(
+            PmdRead::validate(&header[..9]),
+            Err(Error::Parse(ParseError {
+                context: "frame type",
+                ..
+            }))
+        ));
+        assert!(matches!(
+            PmdRead::validate(&[header.as_slice(), &[0xff; 4]].concat()),
+            Err(Error::Parse(ParseError {
+                offset: 13,
+                len: 3,
+                got: 1,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            PmdRead::validate(&[0x07; 13]),
+            Err(Error::InvalidData)
+        ));
+        assert!(matches!(PmdRead::new(vec![0x00]), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn acc_wrong_length() {
+        assert!(matches!(
+            Acc::new(&[0x01; 4]),
+            Err(Error::Parse(ParseError {
+                context: "ACC sample",
+                len: 6,
+                got: 4,
+                ..
+            }))
+        ));
+        assert!(Acc::new(&[]).unwrap_err().is_invalid_length());
+    }
+
+    #[test]
+    fn parse_error_context() {
+        let err = PmdRead::validate(&[0x00, 0xea, 0x54, 0xa2]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Arctic Error: Invalid length, timestamp: needed 8 bytes at offset 1, got 3"
+        );
+
+        assert!(matches!(
+            HeartRate::new(vec![16]),
+            Err(Error::Parse(ParseError {
+                offset: 0,
+                len: 2,
+                got: 1,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn pmd_read_ecg_22_bit() {
+        // 0x3fffff is -1 when read as 22 bits, but 4194303 as 24 bits
+        let packet = [
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0x3f, 0x10,
+            0x00, 0x00,
+        ];
+
+        let ecg = PmdRead::with_ecg_resolution(&packet, 22)
+            .unwrap()
+            .into_ecg()
+            .unwrap();
+        assert_eq!(ecg, vec![Ecg::from_val(-1), Ecg::from_val(16)]);
+
+        let ecg = PmdRead::try_from(packet.as_slice())
+            .unwrap()
+            .into_ecg()
+            .unwrap();
+        assert_eq!(ecg, vec![Ecg::from_val(4_194_303), Ecg::from_val(16)]);
+
+        let ecg = PmdRead::with_ecg_resolution(&packet, 14)
+            .unwrap()
+            .into_ecg()
+            .unwrap();
+        assert_eq!(*ecg[0].val(), 4_194_303);
+    }
+
     #[test]
     fn pmd_read_ecg_new() {
         let response = PmdRead::new(vec![
@@ -261,44 +1221,110 @@ mod test {
         assert_eq!(*response.data_type(), H10MeasurementType::Ecg);
         assert_eq!(response.time_stamp(), 599618164814402794u64);
         let the_data = response.data();
+        assert_eq!(the_data[0].kind(), H10MeasurementType::Ecg);
         match &the_data[0] {
             PmdData::Ecg(thing) => assert_eq!(*thing.val(), -1),
             _ => panic!("Instantiated object of wrong type, expected Ecg"),
         }
     }
 
-    // Test that the converter for acceleration is working properly
     #[test]
-    fn convert_i24_to_i32() {
-        let data = [0xff, 0xff, 0xff];
+    fn timestamp_calibration() {
+        let ecg = |time_stamp: u64| PmdRead {
+            data_type: H10MeasurementType::Ecg,
+            time_stamp,
+            frame_type: PmdFrameType::Raw(0),
+            data: (0..13).map(|_| PmdData::Ecg(Ecg { val: 0 })).collect(),
+        };
+        let mut calibration = TimestampCalibration::new(130);
 
-        assert_eq!(-1, bytes_to_data(&data[..], 3));
+        assert_eq!(calibration.update(&ecg(1_000_000_000)), None);
+        assert_eq!(calibration.to_nanos(5), None);
 
-        let data = [0x00, 0x00, 0x10];
+        // 13 samples at 130 Hz should take 100 ms
+        let tick = calibration.update(&ecg(1_100_000_000)).unwrap();
+        assert!((tick - 1.0).abs() < 1e-9);
+        assert_eq!(calibration.to_nanos(2_000), Some(2_000));
 
-        assert_eq!(1_048_576, bytes_to_data(&data[..], 3));
+        // a clock running in microseconds
+        let mut calibration = TimestampCalibration::new(130);
+        calibration.update(&ecg(1_000_000));
+        let tick = calibration.update(&ecg(1_100_000)).unwrap();
+        assert!((tick - 1000.0).abs() < 1e-6);
     }
 
     #[test]
-    fn convert_i16_to_i32() {
-        let data = [0xff, 0xff];
+    fn sample_history() {
+        let ecg = |vals: &[i32]| PmdRead {
+            data_type: H10MeasurementType::Ecg,
+            time_stamp: 0,
+            frame_type: PmdFrameType::Raw(0),
+            data: vals
+                .iter()
+                .map(|val| PmdData::Ecg(Ecg { val: *val }))
+                .collect(),
+        };
+        let vals = |samples: Vec<Ecg>| samples.iter().map(|e| *e.val()).collect::<Vec<i32>>();
+        let mut history = SampleHistory::default();
 
-        assert_eq!(-1, bytes_to_data(&data[..], 2));
+        history.push(&ecg(&[1, 2]));
+        assert!(history.recent_ecg(5).is_empty());
 
-        let data = [0x00, 0x10];
+        history.set_capacity(3);
+        history.push(&ecg(&[1, 2]));
+        history.push(&ecg(&[3, 4]));
+        assert_eq!(vals(history.recent_ecg(5)), vec![2, 3, 4]);
+        assert_eq!(vals(history.recent_ecg(2)), vec![3, 4]);
+        assert!(history.recent_acc(2).is_empty());
 
-        assert_eq!(4096, bytes_to_data(&data[..], 2));
+        history.set_capacity(1);
+        assert_eq!(vals(history.recent_ecg(5)), vec![4]);
     }
 
     #[test]
-    fn convert_i8_to_i32() {
-        let data = [0xff];
+    fn timestamp_gaps() {
+        let ecg = |time_stamp: u64| {
+            PmdRead::from_parts(
+                H10MeasurementType::Ecg,
+                time_stamp,
+                (0..13).map(|_| PmdData::Ecg(Ecg::from_val(0))).collect(),
+            )
+        };
+        let mut tracker = TimestampTracker::new(130);
+
+        assert_eq!(tracker.update(&ecg(1_000_000_000)), None);
+        // 13 samples at 130 Hz take 100 ms, with a bit of jitter
+        assert_eq!(tracker.update(&ecg(1_100_001_000)), None);
+        // a whole packet went missing
+        assert_eq!(
+            tracker.update(&ecg(1_300_001_000)),
+            Some(TimestampGap {
+                previous_time_stamp: 1_100_001_000,
+                time_stamp: 1_300_001_000,
+                missing_samples: 13,
+            })
+        );
+        assert_eq!(tracker.dropped_samples(), 13);
 
-        assert_eq!(-1, bytes_to_data(&data[..], 1));
+        // microsecond timestamps
+        let mut tracker = TimestampTracker::new(130).with_tick_ns(1000.0);
+        tracker.update(&ecg(1_000_000));
+        assert_eq!(tracker.update(&ecg(1_100_000)), None);
+        assert_eq!(tracker.dropped_samples(), 0);
+    }
 
-        let data = [0x10];
+    #[test]
+    fn acc_in_ms2() {
+        let acc = Acc::from_xyz(Axes3 {
+            x: 1000,
+            y: -500,
+            z: 0,
+        });
+        let (x, y, z) = acc.accel_ms2();
 
-        assert_eq!(16, bytes_to_data(&data[..], 1));
+        assert!((x - 9.80665).abs() < 1e-5);
+        assert!((y + 4.903325).abs() < 1e-5);
+        assert_eq!(z, 0.0);
     }
 
     // Check that acceleration is working properly
@@ -308,5 +1334,100 @@ mod test {
 
         assert_eq!(*hr.bpm(), 60);
         assert_eq!(*hr.rr(), Some(vec![1104, 793]));
+        assert_eq!(hr.rr_slice(), &[1104, 793]);
+
+        assert!(hr.rr_present_flag());
+
+        let hr = HeartRate::new(vec![0, 60]).unwrap();
+        assert!(hr.rr_slice().is_empty());
+        assert!(!hr.rr_present_flag());
+
+        let hr = HeartRate::new(vec![16, 60]).unwrap();
+        assert_eq!(*hr.rr(), None);
+        assert!(hr.rr_present_flag());
+    }
+
+    #[test]
+    fn parse_from_slice() {
+        let packet: &[u8] = &[
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff,
+        ];
+        assert_eq!(
+            PmdRead::try_from(packet).unwrap(),
+            PmdRead::new(packet.to_vec()).unwrap()
+        );
+        assert!(PmdRead::try_from(&packet[..5]).is_err());
+
+        let hr: &[u8] = &[16, 60, 55, 4];
+        assert_eq!(
+            HeartRate::try_from(hr).unwrap(),
+            HeartRate::new(hr.to_vec()).unwrap()
+        );
+        assert!(HeartRate::try_from(&hr[..1]).is_err());
+    }
+
+    #[test]
+    fn hr_contact() {
+        let hr = HeartRate::new(vec![0b110, 60]).unwrap();
+        assert_eq!(hr.sensor_contact(), Some(true));
+        assert_eq!(hr.valid_bpm(), Some(60));
+
+        let hr = HeartRate::new(vec![0b100, 0]).unwrap();
+        assert_eq!(hr.sensor_contact(), Some(false));
+        assert!(hr.contact_lost());
+        assert_eq!(hr.valid_bpm(), None);
+
+        let hr = HeartRate::new(vec![0, 60]).unwrap();
+        assert_eq!(hr.sensor_contact(), None);
+        assert!(!hr.contact_lost());
+        assert_eq!(hr.valid_bpm(), Some(60));
+    }
+
+    #[test]
+    fn spo2() {
+        let spo2 = Spo2::try_from([0x00, 0x62, 0x00, 0x48, 0x00].as_slice()).unwrap();
+        assert_eq!(spo2.spo2(), Some(98.0));
+        assert_eq!(spo2.pulse_rate(), Some(72.0));
+
+        // Unmeasured values are NaN, and optional fields after them are ignored
+        let spo2 = Spo2::try_from([0x01, 0xff, 0x07, 0x48, 0x00, 0x60, 0x00].as_slice()).unwrap();
+        assert_eq!(spo2.spo2(), None);
+        assert_eq!(spo2.pulse_rate(), Some(72.0));
+
+        assert!(matches!(
+            Spo2::try_from([0x00, 0x62, 0x00, 0x48].as_slice()),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn battery_power_state() {
+        assert_eq!(Battery::charging_from_power_state(0b0010_1011), Some(true));
+        assert_eq!(Battery::charging_from_power_state(0b1011_1111), Some(false));
+        assert_eq!(Battery::charging_from_power_state(0b0001_1111), None);
+        assert_eq!(Battery::charging_from_power_state(0), None);
+    }
+
+    #[test]
+    fn hr_implausible_rr() {
+        let hr = HeartRate::new(vec![16, 60, 0x72, 0x0b]).unwrap();
+        assert_eq!(*hr.rr(), Some(vec![3000]));
+
+        let hr = HeartRate::new(vec![16, 60, 0x73, 0x0b]).unwrap();
+        assert_eq!(*hr.bpm(), 60);
+        assert_eq!(*hr.rr(), None);
+        // Used to wrap around to a small interval when cast to u16
+        let hr = HeartRate::new(vec![16, 61, 0x55, 0x04, 0xff, 0xff]).unwrap();
+        assert_eq!(*hr.bpm(), 61);
+        assert_eq!(*hr.rr(), Some(vec![1135]));
+    }
+
+    #[test]
+    fn hr_flags() {
+        let hr = HeartRate::new(vec![0b1001_0110, 60, 0x00, 0x04]).unwrap();
+        assert_eq!(hr.flags(), 0b1001_0110);
+        assert!(hr.rr_present_flag());
+
+        assert_eq!(HeartRate::new(vec![0, 60]).unwrap().flags(), 0);
     }
 }
@@ -0,0 +1,159 @@
+//! # R-peak
+//!
+//! R-peak contains [`RPeakDetector`], which finds heart beats in ECG samples and derives RR
+//! intervals from them, for devices or recordings without the heart rate characteristic.
+//!
+//! Only built with the `rpeak` feature.
+//!
+
+use crate::Biquad;
+
+use std::collections::VecDeque;
+use std::f64::consts::FRAC_1_SQRT_2;
+
+/// Detects R-peaks in a stream of ECG samples, following Pan and Tompkins
+///
+/// Samples are band-passed to 5 - 15 Hz, differentiated, squared and integrated over a 150 ms
+/// window. Peaks of the integrated signal above an adaptive threshold, and at least 200 ms
+/// after the previous beat, are taken as R-peaks. The first two seconds are used to learn the
+/// signal level, so no beats are reported during them.
+#[derive(Debug, Clone)]
+pub struct RPeakDetector {
+    sample_rate: u16,
+    band_pass: [Biquad; 2],
+    last_filtered: f64,
+    window: VecDeque<f64>,
+    window_len: usize,
+    window_sum: f64,
+    // Last two integrated values, to find local maxima
+    previous: [f64; 2],
+    index: u64,
+    learning_max: f64,
+    learning_sum: f64,
+    signal_level: f64,
+    noise_level: f64,
+    last_peak: Option<u64>,
+}
+
+impl RPeakDetector {
+    /// Create a detector for ECG at `sample_rate` Hz (130 Hz on the H10)
+    pub fn new(sample_rate: u16) -> RPeakDetector {
+        let rate = f64::from(sample_rate.max(1));
+        let window_len = ((0.15 * rate).round() as usize).max(1);
+
+        RPeakDetector {
+            sample_rate: sample_rate.max(1),
+            band_pass: [
+                Biquad::high_pass(rate, 5.0, FRAC_1_SQRT_2),
+                Biquad::low_pass(rate, 15.0_f64.min(rate / 2.5), FRAC_1_SQRT_2),
+            ],
+            last_filtered: 0.0,
+            window: VecDeque::with_capacity(window_len),
+            window_len,
+            window_sum: 0.0,
+            previous: [0.0; 2],
+            index: 0,
+            learning_max: 0.0,
+            learning_sum: 0.0,
+            signal_level: 0.0,
+            noise_level: 0.0,
+            last_peak: None,
+        }
+    }
+
+    /// Add an ECG sample (in µV), returning the RR interval (in ms) when it completes a beat
+    ///
+    /// Samples can be raw or already filtered, e.g. with [`crate::EcgFilter::standard`]. Nothing is
+    /// returned for the first beat, since it has no beat before it.
+    pub fn push(&mut self, sample: f64) -> Option<u16> {
+        let filtered = self
+            .band_pass
+            .iter_mut()
+            .fold(sample, |x, stage| stage.process(x));
+        let slope = filtered - self.last_filtered;
+        self.last_filtered = filtered;
+
+        self.window.push_back(slope * slope);
+        self.window_sum += slope * slope;
+        if self.window.len() > self.window_len {
+            self.window_sum -= self.window.pop_front().unwrap_or(0.0);
+        }
+        let integrated = self.window_sum / self.window_len as f64;
+
+        let index = self.index;
+        self.index += 1;
+
+        let learning = u64::from(self.sample_rate) * 2;
+        if index < learning {
+            self.learning_max = self.learning_max.max(integrated);
+            self.learning_sum += integrated;
+            if index + 1 == learning {
+                self.signal_level = self.learning_max / 3.0;
+                self.noise_level = self.learning_sum / learning as f64 / 2.0;
+            }
+            self.previous = [self.previous[1], integrated];
+            return None;
+        }
+
+        // The previous value is a peak if it's higher than the values either side of it
+        let [before, peak] = self.previous;
+        self.previous = [peak, integrated];
+        if !(peak > before && peak >= integrated) {
+            return None;
+        }
+
+        let peak_index = index - 1;
+        let threshold = self.noise_level + 0.25 * (self.signal_level - self.noise_level);
+        let refractory = u64::from(self.sample_rate) / 5;
+        let after_refractory = self
+            .last_peak
+            .is_none_or(|last| peak_index - last >= refractory);
+
+        if peak <= threshold || !after_refractory {
+            self.noise_level = 0.125 * peak + 0.875 * self.noise_level;
+            return None;
+        }
+
+        self.signal_level = 0.125 * peak + 0.875 * self.signal_level;
+        self.last_peak.replace(peak_index).map(|last| {
+            let ms = (peak_index - last) * 1000 / u64::from(self.sample_rate);
+            u16::try_from(ms).unwrap_or(u16::MAX)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // ECG with a narrow QRS complex every `period` samples
+    fn pulses(period: usize, count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|i| match i % period {
+                0 => 1200.0,
+                j if j == 1 || j == period - 1 => 400.0,
+                2 => -300.0,
+                _ => 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detect_rr() {
+        let mut detector = RPeakDetector::new(130);
+        // A beat every 104 samples at 130 Hz is 800 ms
+        let rr: Vec<u16> = pulses(104, 130 * 20)
+            .into_iter()
+            .filter_map(|sample| detector.push(sample))
+            .collect();
+
+        assert!(rr.len() >= 15, "only found {} intervals", rr.len());
+        assert!(rr.iter().all(|rr| (792..=808).contains(rr)), "{:?}", rr);
+    }
+
+    #[test]
+    fn flat_signal() {
+        let mut detector = RPeakDetector::new(130);
+        assert!((0..130 * 10).all(|_| detector.push(0.0).is_none()));
+    }
+}
@@ -0,0 +1,324 @@
+//! # Session
+//!
+//! Session contains [`Session`], which holds everything received during
+//! [`crate::PolarSensor::record_session`], [`SessionStats`] to summarize it, and [`ReplaySpeed`]
+//! to pace replaying it.
+//!
+
+use crate::{async_trait, EventHandler, H10MeasurementType, HeartRate, PmdRead, PolarSensor};
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time;
+
+/// Summary of a [`Session`], made with [`Session::stats`]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SessionStats {
+    /// Average BPM, or [`None`] if there were no valid heart rate updates
+    pub average_bpm: Option<f64>,
+    /// Lowest BPM, or [`None`] if there were no valid heart rate updates
+    pub min_bpm: Option<u8>,
+    /// Highest BPM, or [`None`] if there were no valid heart rate updates
+    pub max_bpm: Option<u8>,
+    /// Number of RR intervals received
+    pub rr_count: usize,
+    /// Number of ECG samples received
+    pub ecg_samples: usize,
+    /// Number of ACC samples received
+    pub acc_samples: usize,
+    /// Time from the start of the session to the last update received
+    pub duration: Duration,
+}
+
+/// How fast [`Session::replay`] dispatches the recorded data
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReplaySpeed {
+    /// Keep the recorded timing, scaled by this multiplier (2.0 replays twice as fast)
+    ///
+    /// A multiplier that isn't a positive number replays instantly.
+    Multiplier(f64),
+    /// Dispatch everything straight away, in the order it was received
+    Instant,
+}
+
+impl ReplaySpeed {
+    /// Replay with the timing it was recorded with
+    pub fn real_time() -> ReplaySpeed {
+        ReplaySpeed::Multiplier(1.0)
+    }
+
+    // How long after the replay starts an entry received at `offset` is dispatched
+    fn delay(&self, offset: Duration) -> Option<Duration> {
+        match *self {
+            ReplaySpeed::Multiplier(speed) if speed > 0.0 => Some(
+                Duration::try_from_secs_f64(offset.as_secs_f64() / speed).unwrap_or(Duration::MAX),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ReplaySpeed {
+    fn default() -> Self {
+        ReplaySpeed::real_time()
+    }
+}
+
+/// Data collected over a recording session
+///
+/// Each entry is paired with how long after the start of the session it was received.
+#[derive(Debug, Clone)]
+pub struct Session {
+    start: SystemTime,
+    heart_rate: Vec<(Duration, HeartRate)>,
+    measurements: Vec<(Duration, PmdRead)>,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            start: SystemTime::now(),
+            heart_rate: vec![],
+            measurements: vec![],
+        }
+    }
+
+    /// Get the time the session started
+    pub fn start(&self) -> SystemTime {
+        self.start
+    }
+
+    /// Get the heart rate updates received during the session
+    pub fn heart_rate(&self) -> &[(Duration, HeartRate)] {
+        &self.heart_rate
+    }
+
+    /// Get the PMD measurements received during the session
+    pub fn measurements(&self) -> &[(Duration, PmdRead)] {
+        &self.measurements
+    }
+
+    /// Returns [`true`] if nothing was received during the session
+    pub fn is_empty(&self) -> bool {
+        self.heart_rate.is_empty() && self.measurements.is_empty()
+    }
+
+    /// Summarize the data received during the session
+    ///
+    /// Heart rate updates sent while the strap had lost contact are left out of the BPM stats.
+    pub fn stats(&self) -> SessionStats {
+        let bpm: Vec<u8> = self
+            .heart_rate
+            .iter()
+            .filter_map(|(_, hr)| hr.valid_bpm())
+            .collect();
+        let count_samples = |ty| -> usize {
+            self.measurements
+                .iter()
+                .filter(|(_, data)| *data.data_type() == ty)
+                .map(|(_, data)| data.samples().len())
+                .sum()
+        };
+        let last_heart_rate = self.heart_rate.last().map(|(offset, _)| *offset);
+        let last_measurement = self.measurements.last().map(|(offset, _)| *offset);
+
+        SessionStats {
+            average_bpm: (!bpm.is_empty())
+                .then(|| bpm.iter().map(|bpm| f64::from(*bpm)).sum::<f64>() / bpm.len() as f64),
+            min_bpm: bpm.iter().min().copied(),
+            max_bpm: bpm.iter().max().copied(),
+            rr_count: self
+                .heart_rate
+                .iter()
+                .map(|(_, hr)| hr.rr_slice().len())
+                .sum(),
+            ecg_samples: count_samples(H10MeasurementType::Ecg),
+            acc_samples: count_samples(H10MeasurementType::Acc),
+            duration: last_heart_rate.max(last_measurement).unwrap_or_default(),
+        }
+    }
+
+    /// Dispatch the recorded data to `eh` in the order it was received, paced by `speed`
+    ///
+    /// Heart rate updates go to [`EventHandler::heart_rate_update`] and measurements to
+    /// [`EventHandler::measurement_update`], with `ctx` passed along to both. Stops early if
+    /// [`EventHandler::should_continue`] returns [`false`].
+    pub async fn replay(&self, ctx: &PolarSensor, eh: &dyn EventHandler, speed: ReplaySpeed) {
+        let mut heart_rate = self.heart_rate.iter().peekable();
+        let mut measurements = self.measurements.iter().peekable();
+        let started = time::Instant::now();
+
+        loop {
+            // Merge both lists by offset, heart rate first when they tie
+            let (offset, next_hr) = match (heart_rate.peek(), measurements.peek()) {
+                (Some((hr, _)), Some((pmd, _))) if hr <= pmd => (*hr, true),
+                (_, Some((pmd, _))) => (*pmd, false),
+                (Some((hr, _)), None) => (*hr, true),
+                (None, None) => break,
+            };
+
+            if let Some(delay) = speed.delay(offset) {
+                if let Some(deadline) = started.checked_add(delay) {
+                    time::sleep_until(deadline).await;
+                }
+            }
+            if !eh.should_continue().await {
+                break;
+            }
+
+            if next_hr {
+                if let Some((_, hr)) = heart_rate.next() {
+                    eh.heart_rate_update(ctx, hr.clone()).await;
+                }
+            } else if let Some((_, data)) = measurements.next() {
+                eh.measurement_update(ctx, data.clone()).await;
+            }
+        }
+    }
+}
+
+// Event handler that fills a [`Session`] with everything it receives
+pub(crate) struct SessionRecorder {
+    started: Instant,
+    session: Mutex<Session>,
+}
+
+impl SessionRecorder {
+    pub(crate) fn new() -> SessionRecorder {
+        SessionRecorder {
+            started: Instant::now(),
+            session: Mutex::new(Session::new()),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Session {
+        self.session.into_inner().unwrap()
+    }
+}
+
+#[async_trait]
+impl EventHandler for SessionRecorder {
+    async fn heart_rate_update(&self, _ctx: &PolarSensor, heartrate: HeartRate) {
+        let elapsed = self.started.elapsed();
+        self.session
+            .lock()
+            .unwrap()
+            .heart_rate
+            .push((elapsed, heartrate));
+    }
+
+    async fn measurement_update(&self, _ctx: &PolarSensor, data: PmdRead) {
+        let elapsed = self.started.elapsed();
+        self.session
+            .lock()
+            .unwrap()
+            .measurements
+            .push((elapsed, data));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[derive(Default)]
+    struct Replayed(Mutex<Vec<&'static str>>);
+
+    #[async_trait]
+    impl EventHandler for Replayed {
+        async fn heart_rate_update(&self, _ctx: &PolarSensor, _heartrate: HeartRate) {
+            self.0.lock().unwrap().push("hr");
+        }
+
+        async fn measurement_update(&self, _ctx: &PolarSensor, _data: PmdRead) {
+            self.0.lock().unwrap().push("pmd");
+        }
+    }
+
+    fn session() -> Session {
+        let hr = HeartRate::new(vec![0, 60]).unwrap();
+        let pmd = PmdRead::new(vec![
+            0x00, 0xea, 0x54, 0xa2, 0x42, 0x8b, 0x45, 0x52, 0x08, 0x00, 0xff, 0xff, 0xff,
+        ])
+        .unwrap();
+
+        Session {
+            start: SystemTime::now(),
+            heart_rate: vec![
+                (Duration::from_secs(0), hr.clone()),
+                (Duration::from_secs(2), hr),
+            ],
+            measurements: vec![(Duration::from_secs(1), pmd)],
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let mut session = session();
+        session.heart_rate.push((
+            Duration::from_secs(3),
+            HeartRate::new(vec![0b10110, 80, 0x00, 0x04, 0x00, 0x02]).unwrap(),
+        ));
+        // Lost contact, so left out of the BPM stats
+        session.heart_rate.push((
+            Duration::from_secs(4),
+            HeartRate::new(vec![0b100, 0]).unwrap(),
+        ));
+
+        assert_eq!(
+            session.stats(),
+            SessionStats {
+                average_bpm: Some((60.0 + 60.0 + 80.0) / 3.0),
+                min_bpm: Some(60),
+                max_bpm: Some(80),
+                rr_count: 2,
+                ecg_samples: 1,
+                acc_samples: 0,
+                duration: Duration::from_secs(4),
+            }
+        );
+
+        let empty = Session::new().stats();
+        assert_eq!(empty, SessionStats::default());
+    }
+
+    #[test]
+    fn replay_in_order() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let handler = Replayed::default();
+
+        let started = Instant::now();
+        aw!(session().replay(&polar, &handler, ReplaySpeed::Instant));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(*handler.0.lock().unwrap(), vec!["hr", "pmd", "hr"]);
+    }
+
+    #[test]
+    fn replay_speed() {
+        let polar = aw!(PolarSensor::new("dummy ID".to_string())).unwrap();
+        let handler = Replayed::default();
+
+        // The last entry is 2 seconds in, so 20x speed takes 100ms
+        let started = Instant::now();
+        aw!(session().replay(&polar, &handler, ReplaySpeed::Multiplier(20.0)));
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_secs(2));
+        assert_eq!(handler.0.lock().unwrap().len(), 3);
+
+        assert_eq!(
+            ReplaySpeed::Multiplier(0.0).delay(Duration::from_secs(1)),
+            None
+        );
+        assert_eq!(
+            ReplaySpeed::Multiplier(f64::NAN).delay(Duration::from_secs(1)),
+            None
+        );
+    }
+}